@@ -0,0 +1,1042 @@
+/*
+ * Static checks over the expanded program that would otherwise only show
+ * up at runtime: references to unbound names, calls to known functions
+ * with the wrong number of arguments, and names bound more than once in
+ * the same parameter list or let. Calls to functions with type annotations
+ * are also checked against them (see types.rs). Top-level definitions that
+ * are never referenced are reported as warnings unless turned off in the
+ * CheckOptions for that module. Pure expressions whose value is thrown
+ * away are reported too (see effect.rs), as are attempts to redefine or
+ * set! a name bound with define-constant.
+ */
+use crate::{
+    doc::docstring,
+    effect::is_pure,
+    form::{form, sym},
+    types::{builtin_type, Type},
+};
+use lust_syntax::read::sexpr::{AtomKind, Root, Sexpr, SexprKind};
+use lust_utils::{intern::InternedString, span::Span};
+use std::{collections::HashMap, fmt::Display};
+
+const BUILTINS: &[&str] = &[
+    "+",
+    "-",
+    "*",
+    "/",
+    "%",
+    "=",
+    "<",
+    ">",
+    "<=",
+    ">=",
+    "not",
+    "head",
+    "tail",
+    "empty?",
+    "pair",
+    "list",
+    "set",
+    "map",
+    "array",
+    "byte-array",
+    "apply",
+    "eval",
+    "read",
+    "read-string",
+    "write-string",
+    "display",
+    "print",
+    "true",
+    "false",
+    "if",
+    "and",
+    "or",
+    "do",
+    "doc",
+    "format",
+];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Severity {
+    Error,
+    Warning,
+}
+
+impl Display for Severity {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Severity::Error => write!(f, "error"),
+            Severity::Warning => write!(f, "warning"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Diagnostic {
+    kind: DiagnosticKind,
+    span: Span,
+    fix: Option<Fix>,
+}
+
+impl Diagnostic {
+    pub fn new(kind: DiagnosticKind, span: Span) -> Self {
+        Self {
+            kind,
+            span,
+            fix: None,
+        }
+    }
+
+    pub fn with_fix(mut self, fix: Fix) -> Self {
+        self.fix = Some(fix);
+        self
+    }
+
+    pub fn fix(&self) -> Option<&Fix> {
+        self.fix.as_ref()
+    }
+
+    pub fn kind(&self) -> &DiagnosticKind {
+        &self.kind
+    }
+
+    pub fn span(&self) -> Span {
+        self.span
+    }
+
+    pub fn severity(&self) -> Severity {
+        match self.kind {
+            DiagnosticKind::UnboundName(_)
+            | DiagnosticKind::ArityMismatch { .. }
+            | DiagnosticKind::DuplicateBinding(_)
+            | DiagnosticKind::ConstantRebound(_)
+            | DiagnosticKind::TypeMismatch { .. }
+            | DiagnosticKind::UnknownType(_) => Severity::Error,
+            DiagnosticKind::IllTyped { .. }
+            | DiagnosticKind::UnusedDefinition(_)
+            | DiagnosticKind::IgnoredValue => Severity::Warning,
+        }
+    }
+}
+
+impl Display for Diagnostic {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}: {}: {}", self.span, self.severity(), self.kind)
+    }
+}
+
+// An edit that resolves a diagnostic, for editors to offer as a quick fix
+#[derive(Debug, Clone, PartialEq)]
+pub struct Fix {
+    pub span: Span,
+    pub replacement: String,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum DiagnosticKind {
+    UnboundName(InternedString),
+    ArityMismatch {
+        name: InternedString,
+        expected: Arity,
+        found: usize,
+    },
+    DuplicateBinding(InternedString),
+    // a definition or set! of a name bound with define-constant
+    ConstantRebound(InternedString),
+    TypeMismatch {
+        expected: Type,
+        found: Type,
+    },
+    UnknownType(InternedString),
+    // a builtin applied to an argument it can't take, e.g. (+ "a" 1)
+    IllTyped {
+        op: InternedString,
+        op_span: Span,
+        expected: Type,
+        found: Type,
+    },
+    UnusedDefinition(InternedString),
+    // a pure expression in statement position, e.g. (+ 1 2) before the end
+    // of a body
+    IgnoredValue,
+}
+
+impl Display for DiagnosticKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            DiagnosticKind::UnboundName(name) => write!(f, "unbound name '{}'", name),
+            DiagnosticKind::ArityMismatch {
+                name,
+                expected,
+                found,
+            } => write!(
+                f,
+                "'{}' expects {} argument(s) but was given {}",
+                name, expected, found
+            ),
+            DiagnosticKind::DuplicateBinding(name) => {
+                write!(f, "'{}' is bound more than once", name)
+            }
+            DiagnosticKind::ConstantRebound(name) => {
+                write!(f, "'{}' is a constant and can't be rebound", name)
+            }
+            DiagnosticKind::TypeMismatch { expected, found } => {
+                write!(f, "expected {} but found {}", expected, found)
+            }
+            DiagnosticKind::UnknownType(name) => write!(f, "unknown type '{}'", name),
+            DiagnosticKind::IllTyped {
+                op,
+                op_span,
+                expected,
+                found,
+            } => write!(
+                f,
+                "'{}' at {} expects {} but was given {}",
+                op, op_span, expected, found
+            ),
+            DiagnosticKind::UnusedDefinition(name) => {
+                write!(f, "'{}' is defined but never used", name)
+            }
+            DiagnosticKind::IgnoredValue => {
+                write!(f, "expression has no effect and its value is ignored")
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Arity {
+    Exact(usize),
+    AtLeast(usize),
+}
+
+impl Arity {
+    pub fn accepts(&self, n: usize) -> bool {
+        match self {
+            Arity::Exact(arity) => n == *arity,
+            Arity::AtLeast(arity) => n >= *arity,
+        }
+    }
+}
+
+impl Display for Arity {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Arity::Exact(n) => write!(f, "{}", n),
+            Arity::AtLeast(n) => write!(f, "at least {}", n),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+struct Binding {
+    arity: Option<Arity>,
+    ty: Type,
+    used: bool,
+    // bound with define-constant
+    constant: bool,
+}
+
+#[derive(Debug, Clone)]
+pub struct CheckOptions {
+    // warn about top-level definitions that are never referenced; names
+    // starting with an underscore are never reported
+    pub warn_unused: bool,
+}
+
+impl Default for CheckOptions {
+    fn default() -> Self {
+        Self { warn_unused: true }
+    }
+}
+
+pub fn check(root: &Root) -> Vec<Diagnostic> {
+    check_with(root, &CheckOptions::default())
+}
+
+pub fn check_with(root: &Root, opts: &CheckOptions) -> Vec<Diagnostic> {
+    Checker::with_options(opts.clone()).check(root)
+}
+
+#[derive(Debug, Default)]
+pub struct Checker {
+    options: CheckOptions,
+    scopes: Vec<HashMap<InternedString, Binding>>,
+    // top-level definitions and the spans of their def forms
+    defs: Vec<(InternedString, Span)>,
+    diagnostics: Vec<Diagnostic>,
+}
+
+impl Checker {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_options(options: CheckOptions) -> Self {
+        Self {
+            options,
+            ..Self::default()
+        }
+    }
+
+    pub fn check(&mut self, root: &Root) -> Vec<Diagnostic> {
+        self.scopes.push(HashMap::new());
+        self.check_body(&root.sexprs.iter().collect::<Vec<_>>());
+        if self.options.warn_unused {
+            self.check_unused();
+        }
+        self.scopes.clear();
+        self.defs.clear();
+        std::mem::take(&mut self.diagnostics)
+    }
+
+    // Definitions in a body can refer to each other, so they're all bound
+    // before any of them is checked. Signatures are applied after, so they
+    // win over types taken from annotated parameters.
+    fn check_body(&mut self, body: &[&Sexpr]) -> Type {
+        for sexpr in body {
+            self.declare(sexpr);
+        }
+        for sexpr in body {
+            self.declare_type(sexpr);
+        }
+        let ty = body
+            .iter()
+            .map(|sexpr| self.check_sexpr(sexpr))
+            .last()
+            .unwrap_or(Type::Any);
+        // top-level expressions are evaluated for the REPL to print
+        if self.scopes.len() > 1 {
+            self.check_statements(body);
+        }
+        ty
+    }
+
+    fn check_statements(&mut self, body: &[&Sexpr]) {
+        if let Some((_, statements)) = body.split_last() {
+            for statement in statements {
+                if is_pure(statement, &|name| self.lookup(name).is_some()) {
+                    self.diagnostics.push(
+                        Diagnostic::new(DiagnosticKind::IgnoredValue, statement.span).with_fix(
+                            Fix {
+                                span: statement.span,
+                                replacement: String::new(),
+                            },
+                        ),
+                    );
+                }
+            }
+        }
+    }
+
+    // (def ...) or (define-constant name value)
+    fn declare(&mut self, sexpr: &Sexpr) {
+        if let Some((head @ ("def" | "define-constant"), args)) = form(sexpr) {
+            let constant = head == "define-constant";
+            let target = args.first().and_then(|arg| match arg.kind.as_ref() {
+                SexprKind::List(sig) | SexprKind::DottedList(sig, _) => sig.head().and_then(sym),
+                _ => sym(arg),
+            });
+            if let Some(name) = target {
+                let existing = self.scopes.last().unwrap().get(&name);
+                if existing.is_some_and(|binding| constant || binding.constant) {
+                    self.diagnostics.push(Diagnostic::new(
+                        DiagnosticKind::ConstantRebound(name),
+                        sexpr.span,
+                    ));
+                }
+            }
+            let name = match args.first().map(|arg| arg.kind.as_ref()) {
+                Some(SexprKind::Atom(_)) => sym(args[0]).inspect(|&name| {
+                    self.define(name, None, Type::Any);
+                }),
+                Some(SexprKind::List(sig)) => {
+                    let mut sig = sig.iter();
+                    sig.next().and_then(sym).inspect(|&name| {
+                        let params = sig.collect::<Vec<_>>();
+                        let ty = self.param_types(&params);
+                        self.define(name, Some(arity(&params)), ty);
+                    })
+                }
+                // (name params... . rest)
+                Some(SexprKind::DottedList(sig, _)) => {
+                    let mut sig = sig.iter();
+                    sig.next().and_then(sym).inspect(|&name| {
+                        let params = sig.collect::<Vec<_>>();
+                        let ty = self.param_types(&params);
+                        self.define(name, Some(Arity::AtLeast(params.len())), ty);
+                    })
+                }
+                _ => None,
+            };
+            if let Some(binding) =
+                name.and_then(|name| self.scopes.last_mut().unwrap().get_mut(&name))
+            {
+                binding.constant = constant;
+            }
+            if let Some(name) = name.filter(|_| self.scopes.len() == 1) {
+                self.defs.push((name, sexpr.span));
+            }
+        }
+    }
+
+    fn check_unused(&mut self) {
+        for (name, span) in std::mem::take(&mut self.defs) {
+            if name.starts_with('_') || self.scopes[0].get(&name).is_none_or(|b| b.used) {
+                continue;
+            }
+            self.diagnostics.push(
+                Diagnostic::new(DiagnosticKind::UnusedDefinition(name), span).with_fix(Fix {
+                    span,
+                    replacement: String::new(),
+                }),
+            );
+        }
+    }
+
+    // (: name type)
+    fn declare_type(&mut self, sexpr: &Sexpr) {
+        if let Some((":", args)) = form(sexpr) {
+            if let [name, ty] = args[..] {
+                let ty = self.parse_type(ty);
+                if let Some(binding) =
+                    sym(name).and_then(|name| self.scopes.last_mut().unwrap().get_mut(&name))
+                {
+                    binding.ty = ty;
+                }
+            }
+        }
+    }
+
+    // A function is only typed if at least one of its parameters is.
+    fn param_types(&mut self, params: &[&Sexpr]) -> Type {
+        if !params.iter().any(|param| annotation(param).is_some()) {
+            return Type::Any;
+        }
+        let params = params
+            .iter()
+            .map(|param| match annotation(param) {
+                Some((_, ty)) => self.parse_type(ty),
+                None => Type::Any,
+            })
+            .collect();
+        Type::Fn(params, Box::new(Type::Any))
+    }
+
+    fn parse_type(&mut self, sexpr: &Sexpr) -> Type {
+        Type::from_sexpr(sexpr).unwrap_or_else(|name| {
+            self.diagnostics.push(Diagnostic::new(
+                DiagnosticKind::UnknownType(name),
+                sexpr.span,
+            ));
+            Type::Any
+        })
+    }
+
+    fn check_sexpr(&mut self, sexpr: &Sexpr) -> Type {
+        match sexpr.kind.as_ref() {
+            SexprKind::Atom(atom) => match atom.kind.as_ref() {
+                AtomKind::Sym(name) => {
+                    self.reference(*name, sexpr.span);
+                    self.lookup(*name)
+                        .map_or(Type::Any, |binding| binding.ty.clone())
+                }
+                AtomKind::Lit(lit) => Type::of_lit(lit),
+                AtomKind::Keyword(_) => Type::Keyword,
+                // module members aren't tracked
+                AtomKind::Path(_) => Type::Any,
+            },
+            SexprKind::List(list) => match form(sexpr) {
+                Some(("quote", args)) => match args.first().map(|arg| arg.kind.as_ref()) {
+                    Some(SexprKind::Atom(atom)) => match atom.kind.as_ref() {
+                        AtomKind::Sym(_) | AtomKind::Path(_) => Type::Sym,
+                        AtomKind::Keyword(_) => Type::Keyword,
+                        AtomKind::Lit(lit) => Type::of_lit(lit),
+                    },
+                    Some(SexprKind::List(_)) => Type::List(Box::new(Type::Any)),
+                    _ => Type::Any,
+                },
+                Some(("quasiquote", args)) => {
+                    for arg in args {
+                        self.check_quasi(arg);
+                    }
+                    Type::Any
+                }
+                Some(("def" | "define-constant", args)) => {
+                    self.check_def(&args);
+                    Type::Any
+                }
+                Some(("set!", args)) => {
+                    if let Some(name) = args.first().and_then(|arg| sym(arg)) {
+                        self.reference(name, args[0].span);
+                        if self.lookup(name).is_some_and(|binding| binding.constant) {
+                            self.diagnostics.push(Diagnostic::new(
+                                DiagnosticKind::ConstantRebound(name),
+                                sexpr.span,
+                            ));
+                        }
+                    }
+                    for arg in args.iter().skip(1) {
+                        self.check_sexpr(arg);
+                    }
+                    Type::Any
+                }
+                Some(("fn", args)) => {
+                    self.check_fn(&args);
+                    Type::Any
+                }
+                Some(("let", args)) => self.check_let(&args),
+                Some(("match", args)) => self.check_match(&args),
+                Some(("if", args)) => args
+                    .iter()
+                    .map(|arg| self.check_sexpr(arg))
+                    .collect::<Vec<_>>()
+                    .into_iter()
+                    .skip(1)
+                    .reduce(Type::join)
+                    .unwrap_or(Type::Any),
+                Some(("do", args)) => {
+                    let ty = args
+                        .iter()
+                        .map(|arg| self.check_sexpr(arg))
+                        .last()
+                        .unwrap_or(Type::Any);
+                    self.check_statements(&args);
+                    ty
+                }
+                Some((":" | "macro" | "reader-macro" | "module", _)) => Type::Any,
+                _ => self.check_apply(&list.iter().collect::<Vec<_>>(), sexpr.span),
+            },
+            SexprKind::DottedList(list, tail) => {
+                for item in list.iter() {
+                    self.check_sexpr(item);
+                }
+                self.check_sexpr(tail);
+                Type::Any
+            }
+            SexprKind::DatumLabel(_, sexpr) => self.check_sexpr(sexpr),
+            SexprKind::DatumRef(_) => Type::Any,
+        }
+    }
+
+    fn check_quasi(&mut self, sexpr: &Sexpr) {
+        match form(sexpr) {
+            Some(("unquote" | "unquote-splicing", args)) => {
+                for arg in args {
+                    self.check_sexpr(arg);
+                }
+            }
+            _ => {
+                if let SexprKind::List(list) = sexpr.kind.as_ref() {
+                    for item in list.iter() {
+                        self.check_quasi(item);
+                    }
+                }
+            }
+        }
+    }
+
+    // (def name value) or (def (name params...) body...), where the
+    // parameters can end with . rest
+    fn check_def(&mut self, args: &[&Sexpr]) {
+        if let Some((sig, body)) = args.split_first() {
+            let (sig, rest) = match sig.kind.as_ref() {
+                SexprKind::List(sig) => (Some(sig), None),
+                SexprKind::DottedList(sig, rest) => (Some(sig), Some(rest)),
+                _ => (None, None),
+            };
+            match sig {
+                Some(sig) => {
+                    let mut sig = sig.iter();
+                    let ty = sig
+                        .next()
+                        .and_then(sym)
+                        .and_then(|name| self.lookup(name))
+                        .map_or(Type::Any, |binding| binding.ty.clone());
+                    let params = sig.collect::<Vec<_>>();
+                    let (types, ret) = match ty {
+                        Type::Fn(types, ret) => (types, *ret),
+                        _ => (vec![], Type::Any),
+                    };
+                    self.scopes.push(HashMap::new());
+                    self.bind_params(&params, &types);
+                    if let Some(rest) = rest {
+                        self.bind_pattern(rest);
+                    }
+                    let body = match docstring(body) {
+                        Some(_) => &body[1..],
+                        None => body,
+                    };
+                    let found = self.check_body(body);
+                    self.scopes.pop();
+                    if let Some(last) = body.last() {
+                        self.expect(&ret, &found, last.span);
+                    }
+                }
+                None => {
+                    let expected = sym(args[0])
+                        .and_then(|name| self.lookup(name))
+                        .map_or(Type::Any, |binding| binding.ty.clone());
+                    for sexpr in body {
+                        let found = self.check_sexpr(sexpr);
+                        self.expect(&expected, &found, sexpr.span);
+                    }
+                }
+            }
+        }
+    }
+
+    // (fn (params...) body...)
+    fn check_fn(&mut self, args: &[&Sexpr]) {
+        if let Some((params, body)) = args.split_first() {
+            self.scopes.push(HashMap::new());
+            match params.kind.as_ref() {
+                SexprKind::List(params) => {
+                    self.bind_params(&params.iter().collect::<Vec<_>>(), &[])
+                }
+                _ => self.bind_pattern(params),
+            }
+            self.check_body(body);
+            self.scopes.pop();
+        }
+    }
+
+    // (let ((name value)...) body...) or (let loop ((name value)...) body...)
+    fn check_let(&mut self, args: &[&Sexpr]) -> Type {
+        let (name, args) = match args.split_first() {
+            Some((first, rest)) if sym(first).is_some() => (sym(first), rest),
+            _ => (None, args),
+        };
+        let Some((bindings, body)) = args.split_first() else {
+            return Type::Any;
+        };
+        let bindings = match bindings.kind.as_ref() {
+            SexprKind::List(bindings) => bindings.iter().collect::<Vec<_>>(),
+            _ => vec![],
+        };
+        let mut names = vec![];
+        for binding in &bindings {
+            if let SexprKind::List(pair) = binding.kind.as_ref() {
+                let mut pair = pair.iter();
+                names.extend(pair.next());
+                for value in pair {
+                    self.check_sexpr(value);
+                }
+            }
+        }
+        self.scopes.push(HashMap::new());
+        if let Some(name) = name {
+            self.define(name, Some(Arity::Exact(names.len())), Type::Any);
+        }
+        for name in names {
+            self.bind_pattern(name);
+        }
+        let ty = self.check_body(body);
+        self.scopes.pop();
+        ty
+    }
+
+    // (match expr (pattern body...)...)
+    fn check_match(&mut self, args: &[&Sexpr]) -> Type {
+        let mut ty = None;
+        if let Some((expr, arms)) = args.split_first() {
+            self.check_sexpr(expr);
+            for arm in arms {
+                if let SexprKind::List(arm) = arm.kind.as_ref() {
+                    let arm = arm.iter().collect::<Vec<_>>();
+                    if let Some((pattern, body)) = arm.split_first() {
+                        self.scopes.push(HashMap::new());
+                        self.bind_pattern(pattern);
+                        let arm_ty = self.check_body(body);
+                        ty = Some(match ty {
+                            Some(ty) => Type::join(ty, arm_ty),
+                            None => arm_ty,
+                        });
+                        self.scopes.pop();
+                    }
+                }
+            }
+        }
+        ty.unwrap_or(Type::Any)
+    }
+
+    fn check_apply(&mut self, items: &[&Sexpr], span: Span) -> Type {
+        let types = items
+            .iter()
+            .map(|item| self.check_sexpr(item))
+            .collect::<Vec<_>>();
+        // () is the empty list
+        let Some((head, args)) = items.split_first() else {
+            return Type::List(Box::new(Type::Any));
+        };
+        let Some(name) = sym(head) else {
+            return Type::Any;
+        };
+        let Some(binding) = self.lookup(name).cloned() else {
+            return self.check_builtin(name, head.span, args, &types[1..]);
+        };
+        let mut checked = args.len();
+        if let Some(arity) = binding.arity {
+            if !arity.accepts(args.len()) {
+                self.diagnostics.push(Diagnostic::new(
+                    DiagnosticKind::ArityMismatch {
+                        name,
+                        expected: arity,
+                        found: args.len(),
+                    },
+                    span,
+                ));
+            }
+            // rest arguments aren't annotated
+            if let Arity::AtLeast(n) = arity {
+                checked = checked.min(n);
+            }
+        }
+        match binding.ty {
+            Type::Fn(params, ret) => {
+                for ((arg, found), expected) in
+                    args.iter().zip(&types[1..]).zip(&params).take(checked)
+                {
+                    self.expect(expected, found, arg.span);
+                }
+                *ret
+            }
+            _ => Type::Any,
+        }
+    }
+
+    // Builtins aren't annotated, but their argument types are known, so
+    // obviously wrong calls can be warned about even in unannotated code.
+    fn check_builtin(
+        &mut self,
+        op: InternedString,
+        op_span: Span,
+        args: &[&Sexpr],
+        types: &[Type],
+    ) -> Type {
+        let Some((expected, ret)) = builtin_type(&op) else {
+            return Type::Any;
+        };
+        for (arg, found) in args.iter().zip(types) {
+            if !expected.accepts(found) {
+                self.diagnostics.push(Diagnostic::new(
+                    DiagnosticKind::IllTyped {
+                        op,
+                        op_span,
+                        expected: expected.clone(),
+                        found: found.clone(),
+                    },
+                    arg.span,
+                ));
+            }
+        }
+        // arithmetic on ints gives an int, so (+ x 1) can still be an Int
+        if ret == Type::Num {
+            return types.iter().cloned().reduce(Type::join).unwrap_or(ret);
+        }
+        ret
+    }
+
+    fn expect(&mut self, expected: &Type, found: &Type, span: Span) {
+        if !expected.accepts(found) {
+            self.diagnostics.push(Diagnostic::new(
+                DiagnosticKind::TypeMismatch {
+                    expected: expected.clone(),
+                    found: found.clone(),
+                },
+                span,
+            ));
+        }
+    }
+
+    fn bind_params(&mut self, params: &[&Sexpr], types: &[Type]) {
+        for (i, param) in params.iter().enumerate() {
+            let ty = types.get(i).cloned().unwrap_or(Type::Any);
+            match (form(param), annotation(param)) {
+                (Some(("varg", args)), _) => {
+                    for arg in args {
+                        self.bind_pattern(arg);
+                    }
+                }
+                (_, Some((name, _))) => self.bind(name, ty, param.span),
+                _ => match sym(param) {
+                    Some(name) => self.bind(name, ty, param.span),
+                    None => self.bind_pattern(param),
+                },
+            }
+        }
+    }
+
+    // binds every symbol in a parameter or match pattern
+    fn bind_pattern(&mut self, pattern: &Sexpr) {
+        match pattern.kind.as_ref() {
+            SexprKind::Atom(atom) => {
+                if let AtomKind::Sym(name) = atom.kind.as_ref() {
+                    self.bind(*name, Type::Any, pattern.span);
+                }
+            }
+            SexprKind::List(list) => {
+                for item in list.iter() {
+                    self.bind_pattern(item);
+                }
+            }
+            SexprKind::DottedList(list, tail) => {
+                for item in list.iter() {
+                    self.bind_pattern(item);
+                }
+                self.bind_pattern(tail);
+            }
+            SexprKind::DatumLabel(_, pattern) => self.bind_pattern(pattern),
+            SexprKind::DatumRef(_) => (),
+        }
+    }
+
+    fn bind(&mut self, name: InternedString, ty: Type, span: Span) {
+        let name = match name.strip_prefix('&') {
+            Some(rest) => InternedString::from(rest),
+            None => name,
+        };
+        if &*name == "_" {
+            return;
+        }
+        if self.scopes.last().unwrap().contains_key(&name) {
+            self.diagnostics.push(Diagnostic::new(
+                DiagnosticKind::DuplicateBinding(name),
+                span,
+            ));
+        } else {
+            self.define(name, None, ty);
+        }
+    }
+
+    fn define(&mut self, name: InternedString, arity: Option<Arity>, ty: Type) {
+        self.scopes.last_mut().unwrap().insert(
+            name,
+            Binding {
+                arity,
+                ty,
+                used: false,
+                constant: false,
+            },
+        );
+    }
+
+    fn lookup(&self, name: InternedString) -> Option<&Binding> {
+        self.scopes.iter().rev().find_map(|scope| scope.get(&name))
+    }
+
+    fn reference(&mut self, name: InternedString, span: Span) {
+        if let Some(binding) = self
+            .scopes
+            .iter_mut()
+            .rev()
+            .find_map(|scope| scope.get_mut(&name))
+        {
+            binding.used = true;
+            return;
+        }
+        // keywords and paths like Map.get aren't variable references
+        if name.contains('.') || BUILTINS.contains(&&*name) {
+            return;
+        }
+        self.diagnostics
+            .push(Diagnostic::new(DiagnosticKind::UnboundName(name), span));
+    }
+}
+
+// (name : type)
+fn annotation(sexpr: &Sexpr) -> Option<(InternedString, &Sexpr)> {
+    match sexpr.kind.as_ref() {
+        SexprKind::List(list) => match list.iter().collect::<Vec<_>>()[..] {
+            [name, colon, ty] if sym(colon).as_deref() == Some(":") => Some((sym(name)?, ty)),
+            _ => None,
+        },
+        _ => None,
+    }
+}
+
+fn arity(params: &[&Sexpr]) -> Arity {
+    let variadic = params.iter().any(|param| {
+        matches!(form(param), Some(("varg", _)))
+            || sym(param).is_some_and(|name| name.starts_with('&'))
+    });
+    if variadic {
+        Arity::AtLeast(params.len() - 1)
+    } else {
+        Arity::Exact(params.len())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{check, check_with, CheckOptions, Fix};
+    use lust_syntax::read::read;
+    use lust_utils::span::Span;
+
+    fn check_str(src: &str) -> Vec<String> {
+        check_str_with(src, &CheckOptions { warn_unused: false })
+    }
+
+    fn check_str_with(src: &str, opts: &CheckOptions) -> Vec<String> {
+        match read(src) {
+            (Some(root), errs) if errs.is_empty() => check_with(&root, opts)
+                .into_iter()
+                .map(|diagnostic| diagnostic.kind().to_string())
+                .collect(),
+            (_, errs) => panic!("failed to read {:?}: {:?}", src, errs),
+        }
+    }
+
+    #[test]
+    fn clean() {
+        let src = "
+            (def (fib n) (if (<= n 1) n (+ (fib (- n 1)) (fib (- n 2)))))
+            (def (fib-iter n)
+              (let loop ((a 0) (b 1) (i n))
+                (if (= i 0) a (loop b (+ a b) (- i 1)))))
+            (def (first xs...) (head xs))
+            (def (f x) (match x (0 1) (n (g n))))
+            (def (g x) `(x ,x))
+            (first 1 2 3)";
+        assert!(check_str(src).is_empty(), "{:?}", check_str(src));
+    }
+
+    #[test]
+    fn unbound() {
+        assert_eq!(check_str("(def (f x) (+ x y))"), ["unbound name 'y'"]);
+        assert_eq!(check_str("(let ((x 1) (y x)) y)"), ["unbound name 'x'"]);
+    }
+
+    #[test]
+    fn arity() {
+        assert_eq!(
+            check_str("(def (f x y) x) (f 1)"),
+            ["'f' expects 2 argument(s) but was given 1"]
+        );
+        assert_eq!(
+            check_str("(def (f x ys...) x) (f)"),
+            ["'f' expects at least 1 argument(s) but was given 0"]
+        );
+        assert_eq!(
+            check_str("(def (f x . rest) (pair x rest)) (f)"),
+            ["'f' expects at least 1 argument(s) but was given 0"]
+        );
+        let src = "(def (f x . rest) (pair x rest)) (f 1 2 3)";
+        assert!(check_str_with(src, &CheckOptions::default()).is_empty());
+    }
+
+    #[test]
+    fn duplicate() {
+        assert_eq!(check_str("(fn (x x) x)"), ["'x' is bound more than once"]);
+        assert_eq!(
+            check_str("(let ((x 1) (x 2)) x)"),
+            ["'x' is bound more than once"]
+        );
+    }
+
+    #[test]
+    fn constants() {
+        assert!(check_str("(define-constant pi 3.14159) (* pi 2)").is_empty());
+        assert_eq!(
+            check_str("(define-constant pi 3.14159) (def pi 3)"),
+            ["'pi' is a constant and can't be rebound"]
+        );
+        assert_eq!(
+            check_str("(define-constant pi 3.14159) (set! pi 3)"),
+            ["'pi' is a constant and can't be rebound"]
+        );
+        // shadowing in an inner scope is fine
+        assert!(check_str("(define-constant pi 3.14159) (let ((pi 3)) (set! pi 4))").is_empty());
+        assert!(check_str("(def x 1) (set! x 2)").is_empty());
+    }
+
+    #[test]
+    fn annotations() {
+        let src = "
+            (: inc (-> Int Int))
+            (def (inc x) (+ x 1))
+            (def (greet (name : String)) name)
+            (def (dynamic x) x)
+            (inc 1)
+            (greet \"lust\")
+            (dynamic 1)
+            (dynamic \"lust\")";
+        assert!(check_str(src).is_empty(), "{:?}", check_str(src));
+        assert_eq!(
+            check_str("(: inc (-> Int Int)) (def (inc x) x) (inc \"a\")"),
+            ["expected Int but found String"]
+        );
+        assert_eq!(
+            check_str("(def (f (x : Int)) x) (f 1.5)"),
+            ["expected Int but found Real"]
+        );
+        assert_eq!(
+            check_str("(: f (-> Int Int)) (def (f x) (* x 1.5))"),
+            ["expected Int but found Num"]
+        );
+        assert_eq!(
+            check_str("(: f (-> Int String)) (def (f x) x)"),
+            ["expected String but found Int"]
+        );
+        assert_eq!(check_str("(: f (-> Foo Int))"), ["unknown type 'Foo'"]);
+    }
+
+    #[test]
+    fn builtins() {
+        assert_eq!(
+            check_str("(head 5)"),
+            ["'head' at 1..5 expects (List Any) but was given Int"]
+        );
+        assert_eq!(
+            check_str("(+ \"a\" 1)"),
+            ["'+' at 1..2 expects Num but was given String"]
+        );
+        assert_eq!(
+            check_str("(+ (< 1 2) 1)"),
+            ["'+' at 1..2 expects Num but was given Bool"]
+        );
+        assert!(check_str("(def (f x) (+ x (head '(1 2))))").is_empty());
+        // a local definition shadows the builtin
+        assert!(check_str("(let ((head (fn (x) x))) (head 5))").is_empty());
+    }
+
+    #[test]
+    fn unused() {
+        let opts = CheckOptions::default();
+        assert_eq!(
+            check_str_with("(def (f x) x) (def (g x) (f x)) (def _h 1)", &opts),
+            ["'g' is defined but never used"]
+        );
+        // shadowing a definition doesn't count as using it
+        assert_eq!(
+            check_str_with("(def x 1) (let ((x 2)) x)", &opts),
+            ["'x' is defined but never used"]
+        );
+        let src = "(def x 1)";
+        let (root, _) = read(src);
+        assert_eq!(
+            check(&root.unwrap())[0].fix(),
+            Some(&Fix {
+                span: Span::from(0..9usize),
+                replacement: String::new(),
+            })
+        );
+    }
+
+    #[test]
+    fn ignored_value() {
+        assert_eq!(
+            check_str("(def (f x) (+ x 1) (print x) x)"),
+            ["expression has no effect and its value is ignored"]
+        );
+        assert_eq!(
+            check_str("(def (f x) (do 'x (print x)))"),
+            ["expression has no effect and its value is ignored"]
+        );
+        assert!(check_str("(def (f x) \"Docs.\" x)").is_empty());
+        assert!(check_str("(def (f x) (let ((+ print)) (+ x) x))").is_empty());
+        assert!(check_str("(+ 1 2) (print 3)").is_empty());
+        assert!(check_str("(def (f x) (write-string x) x)").is_empty());
+    }
+}
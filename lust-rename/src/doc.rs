@@ -0,0 +1,90 @@
+/*
+ * Documentation attached to definitions. A string literal at the start
+ * of a function body, with more body after it, is the function's
+ * docstring:
+ *
+ *     (def (inc x) "Adds one." (+ x 1))
+ *
+ * The REPL's :doc command and the doc generator both read the Docs
+ * collected here.
+ */
+use crate::form::{form, sym};
+use lust_syntax::read::sexpr::{AtomKind, Lit, Root, Sexpr, SexprKind};
+use lust_utils::{intern::InternedString, span::Span};
+use std::fmt::Display;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Doc {
+    pub name: InternedString,
+    // the def's signature, e.g. (inc x), or just the name for values
+    pub signature: String,
+    pub doc: Option<String>,
+    pub span: Span,
+}
+
+impl Display for Doc {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.signature)?;
+        if let Some(doc) = &self.doc {
+            write!(f, "\n  {}", doc)?;
+        }
+        Ok(())
+    }
+}
+
+// Docs for the top-level definitions in `root`.
+pub fn collect(root: &Root) -> Vec<Doc> {
+    root.sexprs.iter().filter_map(doc).collect()
+}
+
+pub fn doc(sexpr: &Sexpr) -> Option<Doc> {
+    let Some(("def", args)) = form(sexpr) else {
+        return None;
+    };
+    let [sig, body @ ..] = &args[..] else {
+        return None;
+    };
+    match sig.kind.as_ref() {
+        SexprKind::List(params) | SexprKind::DottedList(params, _) => Some(Doc {
+            name: params.head().and_then(sym)?,
+            signature: sig.to_string(),
+            doc: docstring(body),
+            span: sexpr.span,
+        }),
+        _ => Some(Doc {
+            name: sym(sig)?,
+            signature: sig.to_string(),
+            doc: None,
+            span: sexpr.span,
+        }),
+    }
+}
+
+pub fn docstring(body: &[&Sexpr]) -> Option<String> {
+    if body.len() < 2 {
+        return None;
+    }
+    match body[0].kind.as_ref() {
+        SexprKind::Atom(atom) => match atom.kind.as_ref() {
+            AtomKind::Lit(Lit::String(s)) => Some(s.to_string()),
+            _ => None,
+        },
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::collect;
+    use lust_syntax::read::read;
+
+    #[test]
+    fn docstrings() {
+        let (root, _) = read(r#"(def (inc x) "Adds one." (+ x 1)) (def (s) "str") (def x 1)"#);
+        let docs = collect(&root.unwrap())
+            .into_iter()
+            .map(|doc| doc.to_string())
+            .collect::<Vec<_>>();
+        assert_eq!(docs, ["(inc x)\n  Adds one.", "(s)", "x"]);
+    }
+}
@@ -0,0 +1,58 @@
+/*
+ * Effect information for builtins and a conservative purity analysis
+ * over expressions. An expression is pure if evaluating it can't do
+ * anything but produce a value, so it's safe to fold at compile time or
+ * to drop when its value isn't used. Calls to anything other than a pure
+ * builtin are assumed to have effects.
+ */
+use lust_syntax::read::sexpr::{AtomKind, Sexpr, SexprKind};
+use lust_utils::intern::InternedString;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Effect {
+    Pure,
+    Impure,
+}
+
+pub fn builtin_effect(name: &str) -> Option<Effect> {
+    match name {
+        "+" | "-" | "*" | "/" | "%" | "=" | "<" | ">" | "<=" | ">=" | "not" | "head" | "tail"
+        | "empty?" | "pair" | "list" | "set" | "map" | "array" | "byte-array" | "format"
+        | "doc" | "read-string" => Some(Effect::Pure),
+        "apply" | "eval" | "read" | "write-string" | "display" | "print" => Some(Effect::Impure),
+        _ => None,
+    }
+}
+
+// `is_local` tells whether a name is bound locally, in which case it
+// shadows any builtin of the same name.
+pub fn is_pure(sexpr: &Sexpr, is_local: &dyn Fn(InternedString) -> bool) -> bool {
+    match sexpr.kind.as_ref() {
+        SexprKind::Atom(_) | SexprKind::DatumRef(_) => true,
+        SexprKind::DatumLabel(_, sexpr) => is_pure(sexpr, is_local),
+        // not a valid call
+        SexprKind::DottedList(..) => false,
+        SexprKind::List(list) => {
+            let mut items = list.iter();
+            let Some(head) = items.next() else {
+                return true;
+            };
+            let name = match head.kind.as_ref() {
+                SexprKind::Atom(atom) => match atom.kind.as_ref() {
+                    AtomKind::Sym(name) => *name,
+                    _ => return false,
+                },
+                _ => return false,
+            };
+            match &*name {
+                "quote" | "fn" => true,
+                "if" | "and" | "or" | "do" => items.all(|item| is_pure(item, is_local)),
+                _ if is_local(name) => false,
+                _ => {
+                    builtin_effect(&name) == Some(Effect::Pure)
+                        && items.all(|item| is_pure(item, is_local))
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,33 @@
+/*
+ * Helpers for taking apart forms, shared by the passes in this crate.
+ */
+use lust_syntax::read::sexpr::{AtomKind, Sexpr, SexprKind};
+use lust_utils::intern::InternedString;
+
+// (name args...), for a list that starts with a symbol
+pub fn form(sexpr: &Sexpr) -> Option<(&str, Vec<&Sexpr>)> {
+    match sexpr.kind.as_ref() {
+        SexprKind::List(list) => {
+            let mut items = list.iter();
+            let head = items.next()?;
+            match head.kind.as_ref() {
+                SexprKind::Atom(atom) => match atom.kind.as_ref() {
+                    AtomKind::Sym(name) => Some((&**name, items.collect())),
+                    _ => None,
+                },
+                _ => None,
+            }
+        }
+        _ => None,
+    }
+}
+
+pub fn sym(sexpr: &Sexpr) -> Option<InternedString> {
+    match sexpr.kind.as_ref() {
+        SexprKind::Atom(atom) => match atom.kind.as_ref() {
+            AtomKind::Sym(name) => Some(*name),
+            _ => None,
+        },
+        _ => None,
+    }
+}
@@ -0,0 +1,7 @@
+pub mod check;
+pub mod doc;
+pub mod effect;
+pub mod tail;
+pub mod types;
+
+mod form;
@@ -7,7 +7,7 @@ use log::trace;
 use num_rational::Rational64;
 use std::{
     cell::{Ref, RefCell},
-    collections::HashMap,
+    collections::BTreeMap,
     fmt::Display,
     rc::Rc,
 };
@@ -58,21 +58,21 @@ pub type ResResult<T> = Result<T, ResError>;
 #[derive(Debug, Clone, PartialEq)]
 pub struct Env {
     parent: Option<Rc<RefCell<Env>>>,
-    data: HashMap<InternedString, UniqueId>,
+    data: BTreeMap<InternedString, UniqueId>,
 }
 
 impl Env {
     pub fn new() -> Rc<RefCell<Self>> {
         Rc::new(RefCell::new(Self {
             parent: None,
-            data: HashMap::new(),
+            data: BTreeMap::new(),
         }))
     }
 
     pub fn new_with_parent(parent: Rc<RefCell<Self>>) -> Rc<RefCell<Self>> {
         Rc::new(RefCell::new(Self {
             parent: Some(parent),
-            data: HashMap::new(),
+            data: BTreeMap::new(),
         }))
     }
 
@@ -0,0 +1,210 @@
+/*
+ * Finds every recursive call (a call to a function from within its own
+ * body, or to a named let from within its loop) and whether it's in tail
+ * position. Calls that aren't in tail position carry the span of the
+ * innermost form that has to do more work after the call returns.
+ */
+use crate::form::sym;
+use lust_syntax::read::sexpr::{Root, Sexpr, SexprKind};
+use lust_utils::{intern::InternedString, span::Span};
+use std::fmt::Display;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct TailCall {
+    pub name: InternedString,
+    pub span: Span,
+    // the enclosing form that makes this a non-tail call
+    pub context: Option<Span>,
+}
+
+impl TailCall {
+    pub fn is_tail(&self) -> bool {
+        self.context.is_none()
+    }
+}
+
+impl Display for TailCall {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self.context {
+            None => write!(f, "{}: tail call to '{}'", self.span, self.name),
+            Some(context) => write!(
+                f,
+                "{}: call to '{}' is not in tail position (inside {})",
+                self.span, self.name, context
+            ),
+        }
+    }
+}
+
+pub fn tail_calls(root: &Root) -> Vec<TailCall> {
+    let mut calls = vec![];
+    for sexpr in root.sexprs.iter() {
+        visit(sexpr, Some(sexpr.span), &[], &mut calls);
+    }
+    calls
+}
+
+// `context` is None when `sexpr` is in tail position of the innermost
+// function in `names`, otherwise the form that uses its value.
+fn visit(
+    sexpr: &Sexpr,
+    context: Option<Span>,
+    names: &[InternedString],
+    calls: &mut Vec<TailCall>,
+) {
+    let SexprKind::List(list) = sexpr.kind.as_ref() else {
+        if let SexprKind::DatumLabel(_, sexpr) = sexpr.kind.as_ref() {
+            visit(sexpr, context, names, calls);
+        }
+        return;
+    };
+    let items = list.iter().collect::<Vec<_>>();
+    let Some((head, args)) = items.split_first() else {
+        return;
+    };
+    let here = Some(sexpr.span);
+    match sym(head).as_deref() {
+        Some("quote" | "quasiquote" | ":" | "macro" | "reader-macro") => (),
+        // (def (name params...) body...)
+        Some("def") => {
+            if let Some((sig, body)) = args.split_first() {
+                match sig.kind.as_ref() {
+                    SexprKind::List(sig) | SexprKind::DottedList(sig, _) => {
+                        let names = sig.head().and_then(sym).into_iter().collect::<Vec<_>>();
+                        visit_body(body, None, sexpr.span, &names, calls);
+                    }
+                    _ => visit_body(body, here, sexpr.span, names, calls),
+                }
+            }
+        }
+        // a lambda's body is the tail of the lambda, not of the function
+        // it appears in
+        Some("fn") => visit_body(args.get(1..).unwrap_or(&[]), None, sexpr.span, &[], calls),
+        Some("if") => {
+            for (i, arg) in args.iter().enumerate() {
+                visit(arg, if i == 0 { here } else { context }, names, calls);
+            }
+        }
+        Some("do" | "and" | "or") => visit_body(args, context, sexpr.span, names, calls),
+        // (let ((name value)...) body...) or (let loop ((name value)...) body...)
+        Some("let") => {
+            let (name, args) = match args.split_first() {
+                Some((first, rest)) if sym(first).is_some() => (sym(first), rest),
+                _ => (None, args),
+            };
+            let Some((bindings, body)) = args.split_first() else {
+                return;
+            };
+            if let SexprKind::List(bindings) = bindings.kind.as_ref() {
+                for binding in bindings.iter() {
+                    if let SexprKind::List(pair) = binding.kind.as_ref() {
+                        for value in pair.iter().skip(1) {
+                            visit(value, here, names, calls);
+                        }
+                    }
+                }
+            }
+            match name {
+                // the loop's body is in tail position of the loop even when
+                // the let itself isn't in tail position of the function
+                Some(name) => {
+                    let mut inner = vec![name];
+                    if context.is_none() {
+                        inner.extend_from_slice(names);
+                    }
+                    visit_body(body, None, sexpr.span, &inner, calls);
+                }
+                None => visit_body(body, context, sexpr.span, names, calls),
+            }
+        }
+        // (match expr (pattern body...)...)
+        Some("match") => {
+            if let Some((expr, arms)) = args.split_first() {
+                visit(expr, here, names, calls);
+                for arm in arms {
+                    if let SexprKind::List(arm) = arm.kind.as_ref() {
+                        let arm = arm.iter().collect::<Vec<_>>();
+                        visit_body(
+                            arm.get(1..).unwrap_or(&[]),
+                            context,
+                            sexpr.span,
+                            names,
+                            calls,
+                        );
+                    }
+                }
+            }
+        }
+        name => {
+            if let Some(name) = name.map(InternedString::from) {
+                if names.contains(&name) {
+                    calls.push(TailCall {
+                        name,
+                        span: sexpr.span,
+                        context,
+                    });
+                }
+            }
+            for item in &items {
+                visit(item, here, names, calls);
+            }
+        }
+    }
+}
+
+// Every expression in a body but the last is followed by the rest of the
+// body, so only the last one can be in tail position.
+fn visit_body(
+    body: &[&Sexpr],
+    context: Option<Span>,
+    enclosing: Span,
+    names: &[InternedString],
+    calls: &mut Vec<TailCall>,
+) {
+    if let Some((last, rest)) = body.split_last() {
+        for sexpr in rest {
+            visit(sexpr, Some(enclosing), names, calls);
+        }
+        visit(last, context, names, calls);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::tail_calls;
+    use lust_syntax::read::read;
+
+    fn report(src: &str) -> Vec<String> {
+        let (root, errs) = read(src);
+        assert!(errs.is_empty(), "{:?}", errs);
+        tail_calls(&root.unwrap())
+            .into_iter()
+            .map(|call| call.to_string())
+            .collect()
+    }
+
+    #[test]
+    fn tail_position() {
+        assert_eq!(
+            report("(def (f n) (if (= n 0) 0 (f (- n 1))))"),
+            ["25..36: tail call to 'f'"]
+        );
+        assert_eq!(
+            report("(def (f n) (if (= n 0) 0 (+ 1 (f (- n 1)))))"),
+            ["30..41: call to 'f' is not in tail position (inside 25..42)"]
+        );
+    }
+
+    #[test]
+    fn named_let() {
+        assert_eq!(
+            report("(def (f n) (let loop ((i n)) (if (= i 0) i (loop (- i 1)))))"),
+            ["43..57: tail call to 'loop'"]
+        );
+    }
+
+    #[test]
+    fn lambda() {
+        assert_eq!(report("(def (f n) (fn (x) (f x)))"), Vec::<String>::new());
+    }
+}
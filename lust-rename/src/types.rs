@@ -0,0 +1,168 @@
+/*
+ * Optional type annotations. A function's type can be declared with a
+ * signature form `(: f (-> Int Int))` or by annotating its parameters
+ * in place `(def (f (x : Int)) ...)`. Anything that isn't annotated has
+ * type Any, which is compatible with every other type, so unannotated
+ * code stays dynamic and is never reported.
+ */
+use lust_syntax::read::sexpr::{AtomKind, Lit, Sexpr, SexprKind};
+use lust_utils::intern::InternedString;
+use std::fmt::Display;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Type {
+    Any,
+    Num,
+    Int,
+    Real,
+    Rational,
+    Complex,
+    Bool,
+    String,
+    Char,
+    Bytes,
+    Sym,
+    Keyword,
+    List(Box<Type>),
+    Fn(Vec<Type>, Box<Type>),
+}
+
+impl Type {
+    pub fn of_lit(lit: &Lit) -> Self {
+        match lit {
+            Lit::Int(_) | Lit::BigInt(_) => Type::Int,
+            Lit::Real(_) => Type::Real,
+            Lit::Rational(_) | Lit::BigRational(_) => Type::Rational,
+            Lit::Complex(_) => Type::Complex,
+            Lit::String(_) => Type::String,
+            Lit::Bool(_) => Type::Bool,
+            Lit::Char(_) => Type::Char,
+            Lit::Bytes(_) => Type::Bytes,
+        }
+    }
+
+    // Parses a type annotation, returning the first name that isn't a type
+    // on failure.
+    pub fn from_sexpr(sexpr: &Sexpr) -> Result<Self, InternedString> {
+        match sexpr.kind.as_ref() {
+            SexprKind::Atom(atom) => match atom.kind.as_ref() {
+                AtomKind::Sym(name) => match &**name {
+                    "Any" => Ok(Type::Any),
+                    "Num" => Ok(Type::Num),
+                    "Int" => Ok(Type::Int),
+                    "Real" => Ok(Type::Real),
+                    "Rational" => Ok(Type::Rational),
+                    "Complex" => Ok(Type::Complex),
+                    "Bool" => Ok(Type::Bool),
+                    "String" => Ok(Type::String),
+                    "Char" => Ok(Type::Char),
+                    "Bytes" => Ok(Type::Bytes),
+                    "Sym" => Ok(Type::Sym),
+                    "Keyword" => Ok(Type::Keyword),
+                    "List" => Ok(Type::List(Box::new(Type::Any))),
+                    _ => Err(*name),
+                },
+                kind => Err(InternedString::from(kind.to_string())),
+            },
+            SexprKind::List(list) => {
+                let items = list.iter().collect::<Vec<_>>();
+                match items.split_first() {
+                    Some((head, args)) => match head.kind.as_ref() {
+                        SexprKind::Atom(atom) => match atom.kind.as_ref() {
+                            AtomKind::Sym(name) if &**name == "->" => match args.split_last() {
+                                Some((ret, params)) => Ok(Type::Fn(
+                                    params
+                                        .iter()
+                                        .map(|param| Type::from_sexpr(param))
+                                        .collect::<Result<_, _>>()?,
+                                    Box::new(Type::from_sexpr(ret)?),
+                                )),
+                                None => Err(*name),
+                            },
+                            AtomKind::Sym(name) if &**name == "List" && args.len() == 1 => {
+                                Ok(Type::List(Box::new(Type::from_sexpr(args[0])?)))
+                            }
+                            AtomKind::Sym(name) => Err(*name),
+                            kind => Err(InternedString::from(kind.to_string())),
+                        },
+                        _ => Err(InternedString::from(head.to_string())),
+                    },
+                    None => Err(InternedString::from("()")),
+                }
+            }
+            _ => Err(InternedString::from(sexpr.to_string())),
+        }
+    }
+
+    // Whether a value of type `other` can be used where `self` is expected.
+    pub fn accepts(&self, other: &Type) -> bool {
+        match (self, other) {
+            (Type::Any, _) | (_, Type::Any) => true,
+            (Type::Num, Type::Int | Type::Real | Type::Rational | Type::Complex) => true,
+            (Type::List(t1), Type::List(t2)) => t1.accepts(t2),
+            (Type::Fn(p1, r1), Type::Fn(p2, r2)) => {
+                p1.len() == p2.len()
+                    && p1.iter().zip(p2).all(|(t1, t2)| t2.accepts(t1))
+                    && r1.accepts(r2)
+            }
+            _ => self == other,
+        }
+    }
+
+    // The most specific type both branches of a conditional share.
+    pub fn join(self, other: Type) -> Type {
+        if self == other {
+            self
+        } else if Type::Num.accepts(&self)
+            && Type::Num.accepts(&other)
+            && self != Type::Any
+            && other != Type::Any
+        {
+            Type::Num
+        } else {
+            Type::Any
+        }
+    }
+}
+
+impl Display for Type {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Type::Any => write!(f, "Any"),
+            Type::Num => write!(f, "Num"),
+            Type::Int => write!(f, "Int"),
+            Type::Real => write!(f, "Real"),
+            Type::Rational => write!(f, "Rational"),
+            Type::Complex => write!(f, "Complex"),
+            Type::Bool => write!(f, "Bool"),
+            Type::String => write!(f, "String"),
+            Type::Char => write!(f, "Char"),
+            Type::Bytes => write!(f, "Bytes"),
+            Type::Sym => write!(f, "Sym"),
+            Type::Keyword => write!(f, "Keyword"),
+            Type::List(t) => write!(f, "(List {})", t),
+            Type::Fn(params, ret) => {
+                write!(f, "(->")?;
+                for param in params {
+                    write!(f, " {}", param)?;
+                }
+                write!(f, " {})", ret)
+            }
+        }
+    }
+}
+
+// The type every argument of a builtin must have and the type of its
+// result, for the builtins that take arguments of a single type.
+pub fn builtin_type(name: &str) -> Option<(Type, Type)> {
+    let list = || Type::List(Box::new(Type::Any));
+    match name {
+        "+" | "-" | "*" | "/" | "%" => Some((Type::Num, Type::Num)),
+        "<" | ">" | "<=" | ">=" => Some((Type::Num, Type::Bool)),
+        "not" => Some((Type::Any, Type::Bool)),
+        "head" => Some((list(), Type::Any)),
+        "tail" => Some((list(), list())),
+        "empty?" => Some((list(), Type::Bool)),
+        _ => None,
+    }
+}
@@ -1,25 +1,106 @@
-use lust_syntax::read::read;
+use lust_rename::doc::collect;
+use lust_runtime::PRELUDE;
+use lust_syntax::{
+    expand::Expander,
+    parse::parse,
+    parse_datum,
+    read::{
+        read, read_bytes,
+        sexpr::{AtomKind, Lit, Root, SexprKind},
+    },
+};
+use lust_utils::intern::InternedString;
 use std::{
-    cell::RefCell,
     collections::HashMap,
     io::{self, Write},
-    rc::Rc,
+    path::PathBuf,
 };
 
+#[derive(Debug, Clone, PartialEq)]
+pub struct ReplOptions {
+    // Lust source loaded before the first prompt, see `Settings`
+    pub rc: Option<PathBuf>,
+}
+
+impl Default for ReplOptions {
+    fn default() -> Self {
+        Self { rc: default_rc() }
+    }
+}
+
+// ~/.lustrc
+pub fn default_rc() -> Option<PathBuf> {
+    std::env::var_os("HOME").map(|home| PathBuf::from(home).join(".lustrc"))
+}
+
 pub fn repl() {
+    repl_with(&ReplOptions::default())
+}
+
+pub fn repl_with(opts: &ReplOptions) {
     let mut src = String::new();
+    let mut docs = HashMap::new();
+    if let (Some(root), _) = read(PRELUDE) {
+        for doc in collect(&root) {
+            docs.insert(doc.name, doc);
+        }
+    }
+    let mut settings = Settings::default();
+    if let Some(root) = opts.rc.as_ref().and_then(load_rc) {
+        for doc in collect(&root) {
+            docs.insert(doc.name, doc);
+        }
+        settings = Settings::from_root(&root);
+    }
     // let mut compiler = Compiler::default();
     // let mut vm = Interpreter::default();
     // let store = Store::new();
     loop {
-        print!("> ");
+        print!("{}", settings.prompt);
         io::stdout().flush().unwrap();
         io::stdin()
             .read_line(&mut src)
             .expect("Failed to read line");
-        match src.trim() {
-            "exit" => break,
-            _ => (),
+        if src.trim() == "exit" {
+            break;
+        }
+        if let Some(name) = src.trim().strip_prefix(":doc ") {
+            match docs.get(&InternedString::from(name.trim())) {
+                Some(doc) => println!("{}", doc),
+                None => println!("no documentation for '{}'", name.trim()),
+            }
+            src.clear();
+            continue;
+        }
+        // prints each step of the expansion in turn
+        if let Some(form) = src.trim().strip_prefix(":expand-step ") {
+            match parse_datum(form) {
+                Ok(mut sexpr) => {
+                    let mut expander = Expander::new();
+                    let mut step = 0;
+                    loop {
+                        match expander.expand_step(&sexpr) {
+                            Some(Ok(next)) => {
+                                step += 1;
+                                println!("{}: {}", step, next);
+                                sexpr = next;
+                            }
+                            Some(Err(err)) => {
+                                println!("{}", err);
+                                break;
+                            }
+                            None if step == 0 => {
+                                println!("nothing to expand");
+                                break;
+                            }
+                            None => break,
+                        }
+                    }
+                }
+                Err(errs) => println!("errs: {:?}", errs),
+            }
+            src.clear();
+            continue;
         }
         let root = match read(&src) {
             (Some(root), errs) => {
@@ -28,6 +109,9 @@ pub fn repl() {
                     println!("errs: {:?}", errs);
                     continue;
                 }
+                for doc in collect(&root) {
+                    docs.insert(doc.name, doc);
+                }
                 root
             }
             (None, errs) => {
@@ -41,7 +125,7 @@ pub fn repl() {
         // }
         // let expanded = expand_macros(store.clone(), &root);
         // println!("expanded: {:#?}", expanded);
-        if let (Some(ast), errors) = parse(root) {
+        if let (Some(ast), errors) = parse(&root) {
             println!("ast: {:#?}", ast);
             if !errors.is_empty() {
                 println!("errors: {:?}", errors);
@@ -52,3 +136,87 @@ pub fn repl() {
         src.clear();
     }
 }
+
+// What an rc file can change. There's no evaluator yet, so a setting is a
+// top level def of a literal, and its definitions are only there for :doc:
+//
+//     (def *prompt* "λ> ")
+#[derive(Debug, Clone, PartialEq)]
+struct Settings {
+    prompt: String,
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Self {
+            prompt: "> ".to_string(),
+        }
+    }
+}
+
+impl Settings {
+    fn from_root(root: &Root) -> Self {
+        let mut settings = Settings::default();
+        for sexpr in &root.sexprs {
+            let SexprKind::List(list) = sexpr.kind.as_ref() else {
+                continue;
+            };
+            let atoms = list
+                .iter()
+                .map(|item| match item.kind.as_ref() {
+                    SexprKind::Atom(atom) => Some(atom.kind.as_ref()),
+                    _ => None,
+                })
+                .collect::<Vec<_>>();
+            let [Some(AtomKind::Sym(def)), Some(AtomKind::Sym(name)), Some(value)] = &atoms[..]
+            else {
+                continue;
+            };
+            if let ("def", "*prompt*", AtomKind::Lit(Lit::String(s))) = (&**def, &**name, value) {
+                settings.prompt = s.to_string()
+            }
+        }
+        settings
+    }
+}
+
+// A missing rc file is fine, one that can't be read is reported and the
+// REPL starts without it.
+fn load_rc(path: &PathBuf) -> Option<Root> {
+    let src = match std::fs::read(path) {
+        Ok(src) => src,
+        Err(err) if err.kind() == io::ErrorKind::NotFound => return None,
+        Err(err) => {
+            eprintln!("{}: {}", path.display(), err);
+            return None;
+        }
+    };
+    let (root, errs) = read_bytes(&src);
+    for err in &errs {
+        eprintln!("{}:{}", path.display(), err);
+    }
+    if !errs.is_empty() {
+        return None;
+    }
+    let (root, errs) = Expander::new().expand(root?, Some(path));
+    for err in &errs {
+        eprintln!("{}:{}", path.display(), err);
+    }
+    Some(root)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Settings;
+    use lust_syntax::read::read;
+
+    #[test]
+    fn settings() {
+        let root = read("(def (inc x) (+ x 1))\n(def *prompt* \"λ> \")")
+            .0
+            .unwrap();
+        assert_eq!(Settings::from_root(&root).prompt, "λ> ");
+        let root = read("(def *prompt* 1)").0.unwrap();
+        assert_eq!(Settings::from_root(&root), Settings::default());
+    }
+}
@@ -0,0 +1,28 @@
+/*
+ * The parts of the standard library written in Lust itself. Their source
+ * is embedded at build time, so they ship inside the binary, and reading
+ * and checking them doubles as a test of the front end.
+ */
+pub const PRELUDE: &str = include_str!("../lib/prelude.lust");
+
+#[cfg(test)]
+mod tests {
+    use super::PRELUDE;
+    use lust_rename::{
+        check::{check_with, CheckOptions},
+        doc::collect,
+    };
+    use lust_syntax::{expand::Expander, read::read};
+
+    #[test]
+    fn prelude() {
+        let (root, errs) = read(PRELUDE);
+        assert!(errs.is_empty(), "{:?}", errs);
+        let (root, errs) = Expander::new().expand(root.unwrap(), None);
+        assert!(errs.is_empty(), "{:?}", errs);
+        // a library's definitions are there to be used elsewhere
+        let diags = check_with(&root, &CheckOptions { warn_unused: false });
+        assert!(diags.is_empty(), "{:?}", diags);
+        assert!(collect(&root).iter().all(|doc| doc.doc.is_some()));
+    }
+}
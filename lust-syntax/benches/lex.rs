@@ -0,0 +1,61 @@
+// Lexing throughput on a symbol-heavy corpus, mostly short names that fit
+// inline in an InternedString plus some long ones that go to the interner,
+// and lexing and reading a generated corpus with a mix of everything.
+use criterion::{black_box, criterion_group, criterion_main, Criterion, Throughput};
+use logos::Logos;
+use lust_syntax::read::{
+    corpus::{generate, CorpusOptions},
+    read,
+    token::Token,
+};
+
+fn corpus() -> String {
+    let names = [
+        "x",
+        "acc",
+        "head",
+        "tail",
+        "map",
+        "filter",
+        "fold-left",
+        "empty?",
+        "string-append",
+        "make-hash-table",
+        "with-output-to-string",
+        "call-with-current-continuation",
+    ];
+    let mut src = String::new();
+    for i in 0..2000 {
+        let name = names[i % names.len()];
+        src.push_str(&format!(
+            "(def ({name}-{i} x y) (if (empty? x) y ({name} (tail x) (pair (head x) y))))\n"
+        ));
+    }
+    src
+}
+
+fn lex(c: &mut Criterion) {
+    let src = corpus();
+    let mut group = c.benchmark_group("lex");
+    group.throughput(Throughput::Bytes(src.len() as u64));
+    group.bench_function("symbols", |b| {
+        b.iter(|| Token::lexer(black_box(&src)).count())
+    });
+    group.finish();
+}
+
+fn generated(c: &mut Criterion) {
+    let src = generate(&CorpusOptions {
+        seed: 1,
+        size: 256 * 1024,
+        ..Default::default()
+    });
+    let mut group = c.benchmark_group("generated");
+    group.throughput(Throughput::Bytes(src.len() as u64));
+    group.bench_function("lex", |b| b.iter(|| Token::lexer(black_box(&src)).count()));
+    group.bench_function("read", |b| b.iter(|| read(black_box(&src))));
+    group.finish();
+}
+
+criterion_group!(benches, lex, generated);
+criterion_main!(benches);
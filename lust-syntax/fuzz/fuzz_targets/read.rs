@@ -0,0 +1,21 @@
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use lust_syntax::read::read;
+
+fuzz_target!(|data: &[u8]| {
+    let Ok(src) = std::str::from_utf8(data) else {
+        return;
+    };
+    // anything that reads cleanly must print to something that reads back
+    // to the same thing
+    if let (Some(root), errs) = read(src) {
+        if errs.is_empty() {
+            let printed = root.to_string();
+            match read(&printed) {
+                (Some(again), errs) if errs.is_empty() => assert_eq!(printed, again.to_string()),
+                (_, errs) => panic!("printed form {:?} failed to read: {:?}", printed, errs),
+            }
+        }
+    }
+});
@@ -0,0 +1,78 @@
+#![no_main]
+
+use libfuzzer_sys::{arbitrary::Arbitrary, fuzz_target};
+use lust_syntax::read::read;
+
+const MAX_DEPTH: usize = 64;
+
+#[derive(Debug, Arbitrary)]
+enum Datum {
+    Int(u32),
+    Bool(bool),
+    Sym(u8),
+    Str(u8),
+    List(Vec<Datum>),
+    Vector(Vec<Datum>),
+    Quote(Box<Datum>),
+    Quasiquote(Box<Datum>),
+    Unquote(Box<Datum>),
+}
+
+impl Datum {
+    fn render(&self, out: &mut String, depth: usize) {
+        if depth > MAX_DEPTH {
+            out.push('x');
+            return;
+        }
+        match self {
+            Datum::Int(n) => out.push_str(&n.to_string()),
+            Datum::Bool(b) => out.push_str(if *b { "#t" } else { "#f" }),
+            Datum::Sym(n) => out.push_str(&format!("s{}", n)),
+            Datum::Str(n) => out.push_str(&format!("\"s{}\"", n)),
+            Datum::List(items) => {
+                out.push('(');
+                Self::render_seq(items, out, depth);
+                out.push(')');
+            }
+            Datum::Vector(items) => {
+                out.push_str("#[");
+                Self::render_seq(items, out, depth);
+                out.push(']');
+            }
+            Datum::Quote(d) => {
+                out.push('\'');
+                d.render(out, depth + 1);
+            }
+            Datum::Quasiquote(d) => {
+                out.push('`');
+                d.render(out, depth + 1);
+            }
+            Datum::Unquote(d) => {
+                out.push(',');
+                d.render(out, depth + 1);
+            }
+        }
+    }
+
+    fn render_seq(items: &[Datum], out: &mut String, depth: usize) {
+        for (i, d) in items.iter().enumerate() {
+            if i != 0 {
+                out.push(' ');
+            }
+            d.render(out, depth + 1);
+        }
+    }
+}
+
+fuzz_target!(|datum: Datum| {
+    let mut src = String::new();
+    datum.render(&mut src, 0);
+    let printed = match read(&src) {
+        (Some(root), errs) if errs.is_empty() => root.to_string(),
+        (_, errs) => panic!("generated datum {:?} failed to read: {:?}", src, errs),
+    };
+    match read(&printed) {
+        (Some(again), errs) if errs.is_empty() => assert_eq!(printed, again.to_string()),
+        (_, errs) => panic!("printed form {:?} failed to read: {:?}", printed, errs),
+    }
+});
@@ -0,0 +1,64 @@
+use crate::read::error::SyntaxError;
+use lust_utils::{intern::InternedString, span::Span};
+use std::{fmt::Display, path::PathBuf};
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ExpandError {
+    kind: ExpandErrorKind,
+    span: Span,
+}
+
+impl ExpandError {
+    pub fn new(kind: ExpandErrorKind, span: Span) -> Self {
+        Self { kind, span }
+    }
+
+    pub fn kind(&self) -> &ExpandErrorKind {
+        &self.kind
+    }
+
+    pub fn span(&self) -> Span {
+        self.span
+    }
+}
+
+impl Display for ExpandError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}: {}", self.span, self.kind)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum ExpandErrorKind {
+    MalformedInclude,
+    IncludeNotFound(PathBuf, String),
+    RecursiveInclude(PathBuf),
+    IncludeSyntax(PathBuf, SyntaxError),
+    MalformedForm(InternedString),
+    // a special form that doesn't have the shape it should, e.g. a let
+    // binding that isn't a (name value) pair
+    InvalidSyntax(&'static str),
+}
+
+impl Display for ExpandErrorKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ExpandErrorKind::MalformedInclude => {
+                write!(f, "include expects a single file name string")
+            }
+            ExpandErrorKind::IncludeNotFound(path, msg) => {
+                write!(f, "cannot include '{}': {}", path.display(), msg)
+            }
+            ExpandErrorKind::RecursiveInclude(path) => {
+                write!(f, "'{}' includes itself", path.display())
+            }
+            ExpandErrorKind::IncludeSyntax(path, err) => {
+                write!(f, "in '{}': {}", path.display(), err)
+            }
+            ExpandErrorKind::MalformedForm(name) => write!(f, "malformed '{}' form", name),
+            ExpandErrorKind::InvalidSyntax(msg) => write!(f, "{}", msg),
+        }
+    }
+}
+
+pub type ExpandResult<T> = std::result::Result<T, ExpandError>;
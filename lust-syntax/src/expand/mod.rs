@@ -0,0 +1,851 @@
+use self::error::{ExpandError, ExpandErrorKind, ExpandResult};
+use crate::read::{
+    read,
+    sexpr::{Atom, AtomKind, Lit, Root, Sexpr, SexprKind},
+};
+use lust_utils::{intern::InternedString, list::List, span::Span};
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+pub mod error;
+
+#[derive(Debug, Default)]
+pub struct Expander {
+    // files currently being expanded, innermost last
+    files: Vec<PathBuf>,
+    errors: Vec<ExpandError>,
+    // counter for generated names
+    temps: usize,
+}
+
+impl Expander {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    // Relative includes in `root` are resolved against the directory of
+    // `path`, or the working directory if there is none.
+    pub fn expand(&mut self, root: Root, path: Option<&Path>) -> (Root, Vec<ExpandError>) {
+        self.files
+            .extend(path.map(|path| fs::canonicalize(path).unwrap_or(path.to_path_buf())));
+        let sexprs = self.expand_seq(root.sexprs);
+        self.files.clear();
+        (Root { sexprs, ..root }, std::mem::take(&mut self.errors))
+    }
+
+    // A single rewrite of the outermost built-in form in `sexpr`, searching
+    // depth first, for stepping through an expansion. Returns None once
+    // there's nothing left to rewrite. Destructuring isn't a step.
+    pub fn expand_step(&mut self, sexpr: &Sexpr) -> Option<ExpandResult<Sexpr>> {
+        if is_quoted(sexpr) || is_type(sexpr) {
+            return None;
+        }
+        if let Some(result) = self.thread(sexpr).or_else(|| self.comprehension(sexpr)) {
+            return Some(result);
+        }
+        let SexprKind::List(items) = sexpr.kind.as_ref() else {
+            return None;
+        };
+        let mut items = items.iter().cloned().collect::<Vec<_>>();
+        for i in 0..items.len() {
+            if let Some(result) = self.expand_step(&items[i]) {
+                return Some(result.map(|item| {
+                    items[i] = item;
+                    list(items, sexpr.span)
+                }));
+            }
+        }
+        None
+    }
+
+    fn expand_seq(&mut self, sexprs: Vec<Sexpr>) -> Vec<Sexpr> {
+        let mut out = vec![];
+        for sexpr in sexprs {
+            match include_path(&sexpr) {
+                Some(Ok(path)) => out.extend(self.include(&path, &sexpr)),
+                Some(Err(err)) => self.errors.push(err),
+                None => {
+                    let sexpr = self.expand_sexpr(sexpr);
+                    out.extend(self.destructure_def(sexpr));
+                }
+            }
+        }
+        out
+    }
+
+    fn expand_sexpr(&mut self, sexpr: Sexpr) -> Sexpr {
+        // type annotations use -> for function types
+        if is_quoted(&sexpr) || is_type(&sexpr) {
+            return sexpr;
+        }
+        match self.thread(&sexpr).or_else(|| self.comprehension(&sexpr)) {
+            Some(Ok(sexpr)) => return self.expand_sexpr(sexpr),
+            Some(Err(err)) => {
+                self.errors.push(err);
+                return sexpr;
+            }
+            None => (),
+        }
+        self.validate(&sexpr);
+        let span = sexpr.span;
+        let is_let = head_sym(&sexpr) == Some("let");
+        match sexpr.into_kind() {
+            SexprKind::List(list) => {
+                let mut items = self.expand_seq(list.iter().cloned().collect());
+                if is_let {
+                    items = self.destructure_let(items);
+                }
+                Sexpr::new(SexprKind::List(List::from(items)), span)
+            }
+            SexprKind::DatumLabel(n, inner) => {
+                Sexpr::new(SexprKind::DatumLabel(n, self.expand_sexpr(inner)), span)
+            }
+            kind => Sexpr::new(kind, span),
+        }
+    }
+
+    // Checks the shape of special forms so that malformed ones are
+    // reported here, at the part that's wrong, rather than later on.
+    fn validate(&mut self, sexpr: &Sexpr) {
+        let Some(form) = head_sym(sexpr) else {
+            return;
+        };
+        let args = match sexpr.kind.as_ref() {
+            SexprKind::List(list) => list.iter().skip(1).collect::<Vec<_>>(),
+            _ => unreachable!(),
+        };
+        match form {
+            "def" if args.len() < 2 => {
+                self.invalid("def needs a name and a value", sexpr.span);
+            }
+            "fn" => match args.first() {
+                Some(params) if args.len() >= 2 => match params.kind.as_ref() {
+                    SexprKind::List(params) => {
+                        for param in params.iter() {
+                            if is_lit(param) {
+                                self.invalid("fn parameter must be a name or pattern", param.span);
+                            }
+                        }
+                    }
+                    // (x y . rest)
+                    SexprKind::DottedList(params, rest) => {
+                        for param in params.iter() {
+                            if is_lit(param) {
+                                self.invalid("fn parameter must be a name or pattern", param.span);
+                            }
+                        }
+                        if !is_sym(rest) {
+                            self.invalid("fn rest parameter must be a name", rest.span);
+                        }
+                    }
+                    _ if is_sym(params) => (),
+                    _ => self.invalid("fn parameters must be a list of names", params.span),
+                },
+                _ => self.invalid("fn needs a parameter list and a body", sexpr.span),
+            },
+            "let" => {
+                // named let
+                let args = match args.split_first() {
+                    Some((name, rest)) if is_sym(name) => rest,
+                    _ => &args[..],
+                };
+                let Some((bindings, body)) = args.split_first() else {
+                    self.invalid("let needs bindings and a body", sexpr.span);
+                    return;
+                };
+                match bindings.kind.as_ref() {
+                    SexprKind::List(bindings) => {
+                        for binding in bindings.iter() {
+                            let ok = match binding.kind.as_ref() {
+                                SexprKind::List(pair) => {
+                                    pair.iter().count() == 2 && !pair.head().is_none_or(is_lit)
+                                }
+                                _ => false,
+                            };
+                            if !ok {
+                                self.invalid(
+                                    "let binding must be a (name value) pair",
+                                    binding.span,
+                                );
+                            }
+                        }
+                    }
+                    _ => self.invalid(
+                        "let bindings must be a list of (name value) pairs",
+                        bindings.span,
+                    ),
+                }
+                if body.is_empty() {
+                    self.invalid("let needs a body", sexpr.span);
+                }
+            }
+            "cond" => {
+                for clause in args {
+                    if !matches!(clause.kind.as_ref(), SexprKind::List(_)) {
+                        self.invalid("cond clause must be a (test body...) list", clause.span);
+                    }
+                }
+            }
+            _ => (),
+        }
+    }
+
+    fn invalid(&mut self, msg: &'static str, span: Span) {
+        self.errors
+            .push(ExpandError::new(ExpandErrorKind::InvalidSyntax(msg), span));
+    }
+
+    // A let binding whose name is a list is a pattern, bound by matching
+    // against the value:
+    //   (let (((x y) pair)) body...)
+    // becomes
+    //   (let ((#0 pair)) (match #0 ((x y) body...)))
+    fn destructure_let(&mut self, mut items: Vec<Sexpr>) -> Vec<Sexpr> {
+        // named let parameters can't be patterns
+        let Some(SexprKind::List(bindings)) = items.get(1).map(|b| b.kind.as_ref()) else {
+            return items;
+        };
+        let mut patterns = vec![];
+        let mut new_bindings = vec![];
+        for binding in bindings.iter() {
+            let mut binding = binding.clone();
+            if let SexprKind::List(pair) = binding.kind.as_mut() {
+                if let Some(SexprKind::List(_)) = pair.head().map(|p| p.kind.as_ref()) {
+                    let mut rest = pair.iter().cloned().collect::<Vec<_>>();
+                    let pattern = rest.remove(0);
+                    let temp = self.temp(pattern.span);
+                    rest.insert(0, temp.clone());
+                    *pair = List::from(rest);
+                    patterns.push((temp, pattern));
+                }
+            }
+            new_bindings.push(binding);
+        }
+        if patterns.is_empty() {
+            return items;
+        }
+        let span = items[1].span;
+        items[1] = Sexpr::new(SexprKind::List(List::from(new_bindings)), span);
+        let mut body = items.split_off(2);
+        for (temp, pattern) in patterns.into_iter().rev() {
+            let span = pattern.span;
+            let mut arm = vec![pattern];
+            arm.extend(body);
+            let arm = Sexpr::new(SexprKind::List(List::from(arm)), span);
+            let form = vec![sym("match", span), temp, arm];
+            body = vec![Sexpr::new(SexprKind::List(List::from(form)), span)];
+        }
+        items.extend(body);
+        items
+    }
+
+    // A def whose pattern can't be a function signature, because it
+    // doesn't start with a name, defines each name in the pattern by
+    // matching against the value:
+    //   (def ((a b) . rest) xs)
+    // becomes
+    //   (def #0 xs)
+    //   (def a (match #0 (((a b) . rest) a)))
+    //   (def b (match #0 (((a b) . rest) b)))
+    //   (def rest (match #0 (((a b) . rest) rest)))
+    // (def (f x) ...) and (def (f x . rest) ...) are still functions.
+    fn destructure_def(&mut self, sexpr: Sexpr) -> Vec<Sexpr> {
+        if head_sym(&sexpr) != Some("def") {
+            return vec![sexpr];
+        }
+        let items = match sexpr.kind.as_ref() {
+            SexprKind::List(list) => list.iter().cloned().collect::<Vec<_>>(),
+            _ => unreachable!(),
+        };
+        let Some(pattern) = items.get(1) else {
+            return vec![sexpr];
+        };
+        let is_pattern = match pattern.kind.as_ref() {
+            SexprKind::List(list) | SexprKind::DottedList(list, _) => {
+                list.head().is_some_and(|head| !is_sym(head))
+            }
+            _ => false,
+        };
+        if !is_pattern {
+            return vec![sexpr];
+        }
+        let [def, pattern, value] = &items[..] else {
+            self.invalid("def with a pattern needs exactly one value", sexpr.span);
+            return vec![sexpr];
+        };
+        let span = sexpr.span;
+        let temp = self.temp(pattern.span);
+        let mut out = vec![list(vec![def.clone(), temp.clone(), value.clone()], span)];
+        let mut names = vec![];
+        pattern_names(pattern, &mut names);
+        for name in names {
+            let arm = list(vec![pattern.clone(), name.clone()], pattern.span);
+            let value = list(vec![sym("match", span), temp.clone(), arm], span);
+            out.push(list(vec![def.clone(), name, value], span));
+        }
+        out
+    }
+
+    // Threading forms, until there's a macro system to define them in the
+    // prelude:
+    //   (-> x (f a) g)          => (g (f x a))
+    //   (->> x (f a) g)         => (g (f a x))
+    //   (as-> x n (f n) (g n))  => (let ((n x)) (let ((n (f n))) (g n)))
+    //   (doto x (f a) (g b))    => (let ((#0 x)) (f #0 a) (g #0 b) #0)
+    fn thread(&mut self, sexpr: &Sexpr) -> Option<ExpandResult<Sexpr>> {
+        let op = head_sym(sexpr)?;
+        if !matches!(op, "->" | "->>" | "as->" | "doto") {
+            return None;
+        }
+        let span = sexpr.span;
+        let malformed = || {
+            ExpandError::new(
+                ExpandErrorKind::MalformedForm(InternedString::from(op)),
+                span,
+            )
+        };
+        let args = match sexpr.kind.as_ref() {
+            SexprKind::List(list) => list.iter().skip(1).collect::<Vec<_>>(),
+            _ => unreachable!(),
+        };
+        let Some((x, rest)) = args.split_first() else {
+            return Some(Err(malformed()));
+        };
+        Some(match op {
+            "->" | "->>" => Ok(rest.iter().fold((*x).clone(), |acc, step| {
+                let mut items = step_items(step);
+                if op == "->" {
+                    items.insert(items.len().min(1), acc);
+                } else {
+                    items.push(acc);
+                }
+                list(items, step.span)
+            })),
+            "as->" => match rest.split_first() {
+                Some((name, forms)) if is_sym(name) => {
+                    let (body, forms) = match forms.split_last() {
+                        Some((last, forms)) => ((*last).clone(), forms),
+                        None => ((*name).clone(), forms),
+                    };
+                    let values = std::iter::once(x).chain(forms);
+                    Ok(values.rev().fold(body, |body, value| {
+                        let binding = list(vec![(*name).clone(), (*value).clone()], value.span);
+                        list(
+                            vec![sym("let", span), list(vec![binding], value.span), body],
+                            span,
+                        )
+                    }))
+                }
+                _ => Err(malformed()),
+            },
+            _ => {
+                let temp = self.temp(x.span);
+                let binding = list(vec![temp.clone(), (*x).clone()], x.span);
+                let mut items = vec![sym("let", span), list(vec![binding], x.span)];
+                for step in rest {
+                    let mut call = step_items(step);
+                    call.insert(call.len().min(1), temp.clone());
+                    items.push(list(call, step.span));
+                }
+                items.push(temp);
+                Ok(list(items, span))
+            }
+        })
+    }
+
+    // List comprehensions, where each clause either binds a name to the
+    // elements of a list or filters with #:when. Each binding is a named let
+    // loop that conses onto an accumulator, and a last loop reverses it, so
+    // the expansion only uses built-ins and works without the prelude. The
+    // accumulator is a generated name, so each loop's shadows the one
+    // outside it:
+    //   (for ((x xs) #:when (p x)) (f x))
+    //     => (let #3 ((#4 (let ((#0 ()))
+    //                       (let #1 ((#2 xs) (#0 #0))
+    //                         (if (empty? #2)
+    //                             #0
+    //                             (#1 (tail #2) (let ((x (head #2))) (if (p x) (pair (f x) #0) #0)))))))
+    //                 (#0 ()))
+    //          (if (empty? #4) #0 (#3 (tail #4) (pair (head #4) #0))))
+    fn comprehension(&mut self, sexpr: &Sexpr) -> Option<ExpandResult<Sexpr>> {
+        if head_sym(sexpr) != Some("for") {
+            return None;
+        }
+        let span = sexpr.span;
+        let malformed = |span| {
+            ExpandError::new(
+                ExpandErrorKind::MalformedForm(InternedString::from("for")),
+                span,
+            )
+        };
+        let args = match sexpr.kind.as_ref() {
+            SexprKind::List(list) => list.iter().skip(1).collect::<Vec<_>>(),
+            _ => unreachable!(),
+        };
+        let [clauses, body] = args[..] else {
+            return Some(Err(malformed(span)));
+        };
+        let SexprKind::List(clauses) = clauses.kind.as_ref() else {
+            return Some(Err(malformed(clauses.span)));
+        };
+        // (Some(name), list) for a binding, (None, test) for a filter
+        let mut steps = vec![];
+        let mut clauses = clauses.iter();
+        while let Some(clause) = clauses.next() {
+            if is_when(clause) {
+                match clauses.next() {
+                    Some(test) => steps.push((None, test)),
+                    None => return Some(Err(malformed(clause.span))),
+                }
+                continue;
+            }
+            let binding = match clause.kind.as_ref() {
+                SexprKind::List(binding) => binding.iter().collect::<Vec<_>>(),
+                _ => vec![],
+            };
+            match binding[..] {
+                [name, xs] if is_sym(name) => steps.push((Some(name), xs)),
+                _ => return Some(Err(malformed(clause.span))),
+            }
+        }
+        let acc = self.temp(span);
+        // (let name ((rest xs) (acc init)) (if (empty? rest) acc (name (tail rest) next)))
+        // where next can use (head rest)
+        let each = |name: Sexpr, rest: Sexpr, xs: Sexpr, init: Sexpr, next: Sexpr, span| {
+            let bindings = list(
+                vec![
+                    list(vec![rest.clone(), xs], span),
+                    list(vec![acc.clone(), init], span),
+                ],
+                span,
+            );
+            let empty = list(vec![sym("empty?", span), rest.clone()], span);
+            let tail = list(vec![sym("tail", span), rest], span);
+            let recur = list(vec![name.clone(), tail, next], span);
+            let body = list(vec![sym("if", span), empty, acc.clone(), recur], span);
+            list(vec![sym("let", span), name, bindings, body], span)
+        };
+        let steps = steps
+            .into_iter()
+            .map(|(name, xs)| {
+                let names = name.map(|name| (name, self.temp(name.span), self.temp(name.span)));
+                (names, xs)
+            })
+            .collect::<Vec<_>>();
+        let inner = list(
+            vec![sym("pair", body.span), body.clone(), acc.clone()],
+            body.span,
+        );
+        let folded = steps.into_iter().rev().fold(inner, |inner, step| {
+            let span = step.1.span;
+            match step {
+                (Some((name, loop_name, rest)), xs) => {
+                    let head = list(vec![sym("head", span), rest.clone()], span);
+                    let binding = list(vec![list(vec![name.clone(), head], span)], span);
+                    let next = list(vec![sym("let", span), binding, inner], span);
+                    each(loop_name, rest, xs.clone(), acc.clone(), next, span)
+                }
+                (None, test) => list(
+                    vec![sym("if", span), test.clone(), inner, acc.clone()],
+                    span,
+                ),
+            }
+        });
+        let binding = list(vec![acc.clone(), list(vec![], span)], span);
+        let init = list(
+            vec![sym("let", span), list(vec![binding], span), folded],
+            span,
+        );
+        let (loop_name, rest) = (self.temp(span), self.temp(span));
+        let head = list(vec![sym("head", span), rest.clone()], span);
+        let next = list(vec![sym("pair", span), head, acc.clone()], span);
+        Some(Ok(each(
+            loop_name,
+            rest,
+            init,
+            list(vec![], span),
+            next,
+            span,
+        )))
+    }
+
+    fn temp(&mut self, span: Span) -> Sexpr {
+        // '#' can't start a symbol in source, so these can't be captured
+        let name = format!("#{}", self.temps);
+        self.temps += 1;
+        sym(&name, span)
+    }
+
+    fn include(&mut self, path: &Path, form: &Sexpr) -> Vec<Sexpr> {
+        let path = match self.files.last().and_then(|file| file.parent()) {
+            Some(dir) => dir.join(path),
+            None => path.to_path_buf(),
+        };
+        // canonical, so a/../a/b.lust is caught as the same file as a/b.lust
+        let path = match fs::canonicalize(&path) {
+            Ok(path) => path,
+            Err(err) => {
+                self.errors.push(ExpandError::new(
+                    ExpandErrorKind::IncludeNotFound(path, err.to_string()),
+                    form.span,
+                ));
+                return vec![];
+            }
+        };
+        if self.files.contains(&path) {
+            self.errors.push(ExpandError::new(
+                ExpandErrorKind::RecursiveInclude(path),
+                form.span,
+            ));
+            return vec![];
+        }
+        let src = match fs::read_to_string(&path) {
+            Ok(src) => src,
+            Err(err) => {
+                self.errors.push(ExpandError::new(
+                    ExpandErrorKind::IncludeNotFound(path, err.to_string()),
+                    form.span,
+                ));
+                return vec![];
+            }
+        };
+        let (root, errs) = read(&src);
+        for err in errs {
+            self.errors.push(ExpandError::new(
+                ExpandErrorKind::IncludeSyntax(path.clone(), err),
+                form.span,
+            ));
+        }
+        let Some(root) = root else {
+            return vec![];
+        };
+        self.files.push(path);
+        let sexprs = self.expand_seq(root.sexprs);
+        self.files.pop();
+        sexprs
+    }
+}
+
+fn head_sym(sexpr: &Sexpr) -> Option<&str> {
+    match sexpr.kind.as_ref() {
+        SexprKind::List(list) => match list.head()?.kind.as_ref() {
+            SexprKind::Atom(atom) => match atom.kind.as_ref() {
+                AtomKind::Sym(name) => Some(&**name),
+                _ => None,
+            },
+            _ => None,
+        },
+        _ => None,
+    }
+}
+
+fn sym(name: &str, span: Span) -> Sexpr {
+    Sexpr::new(
+        SexprKind::Atom(Atom::new(AtomKind::Sym(InternedString::from(name)), span)),
+        span,
+    )
+}
+
+fn list(items: Vec<Sexpr>, span: Span) -> Sexpr {
+    Sexpr::new(SexprKind::List(List::from(items)), span)
+}
+
+fn is_lit(sexpr: &Sexpr) -> bool {
+    match sexpr.kind.as_ref() {
+        SexprKind::Atom(atom) => matches!(atom.kind.as_ref(), AtomKind::Lit(_)),
+        _ => false,
+    }
+}
+
+fn is_sym(sexpr: &Sexpr) -> bool {
+    match sexpr.kind.as_ref() {
+        SexprKind::Atom(atom) => matches!(atom.kind.as_ref(), AtomKind::Sym(_)),
+        _ => false,
+    }
+}
+
+// the names a pattern binds, in order
+fn pattern_names(pattern: &Sexpr, names: &mut Vec<Sexpr>) {
+    match pattern.kind.as_ref() {
+        SexprKind::Atom(atom) => {
+            if let AtomKind::Sym(name) = atom.kind.as_ref() {
+                let name = name.strip_prefix('&').unwrap_or(name);
+                if name != "_" {
+                    names.push(sym(name, pattern.span));
+                }
+            }
+        }
+        SexprKind::List(list) => {
+            let skip = usize::from(head_sym(pattern) == Some("varg"));
+            for item in list.iter().skip(skip) {
+                pattern_names(item, names);
+            }
+        }
+        SexprKind::DottedList(list, tail) => {
+            for item in list.iter() {
+                pattern_names(item, names);
+            }
+            pattern_names(tail, names);
+        }
+        SexprKind::DatumLabel(_, pattern) => pattern_names(pattern, names),
+        SexprKind::DatumRef(_) => (),
+    }
+}
+
+// a threading step is either a call to add an argument to or a bare
+// function name
+fn step_items(step: &Sexpr) -> Vec<Sexpr> {
+    match step.kind.as_ref() {
+        SexprKind::List(list) if !is_quoted(step) => list.iter().cloned().collect(),
+        _ => vec![step.clone()],
+    }
+}
+
+fn is_when(sexpr: &Sexpr) -> bool {
+    match sexpr.kind.as_ref() {
+        SexprKind::Atom(atom) => {
+            matches!(atom.kind.as_ref(), AtomKind::Keyword(name) if &**name == "when")
+        }
+        _ => false,
+    }
+}
+
+fn is_quoted(sexpr: &Sexpr) -> bool {
+    matches!(head_sym(sexpr), Some("quote" | "quasiquote"))
+}
+
+// a signature (: f type) or an annotated parameter (x : type)
+fn is_type(sexpr: &Sexpr) -> bool {
+    match sexpr.kind.as_ref() {
+        SexprKind::List(list) => match list.iter().collect::<Vec<_>>()[..] {
+            [colon, ..] if is_colon(colon) => true,
+            [_, colon, _] => is_colon(colon),
+            _ => false,
+        },
+        _ => false,
+    }
+}
+
+fn is_colon(sexpr: &Sexpr) -> bool {
+    match sexpr.kind.as_ref() {
+        SexprKind::Atom(atom) => {
+            matches!(atom.kind.as_ref(), AtomKind::Sym(name) if &**name == ":")
+        }
+        _ => false,
+    }
+}
+
+// (include "file")
+fn include_path(sexpr: &Sexpr) -> Option<ExpandResult<PathBuf>> {
+    if head_sym(sexpr) != Some("include") {
+        return None;
+    }
+    let args = match sexpr.kind.as_ref() {
+        SexprKind::List(list) => list.iter().skip(1).collect::<Vec<_>>(),
+        _ => unreachable!(),
+    };
+    let name = match args.as_slice() {
+        [arg] => match arg.kind.as_ref() {
+            SexprKind::Atom(atom) => match atom.kind.as_ref() {
+                AtomKind::Lit(Lit::String(name)) => Some(name.to_string()),
+                _ => None,
+            },
+            _ => None,
+        },
+        _ => None,
+    };
+    Some(name.map(PathBuf::from).ok_or(ExpandError::new(
+        ExpandErrorKind::MalformedInclude,
+        sexpr.span,
+    )))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{error::ExpandErrorKind, Expander};
+    use crate::read::read;
+    use std::{env, fs};
+
+    #[test]
+    fn include() {
+        let dir = env::temp_dir().join(format!("lust-include-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(dir.join("lib.lust"), "(def y 2) (include \"lib.lust\")").unwrap();
+        let main = dir.join("main.lust");
+        let src = "(def x 1) (do (include \"lib.lust\") '(include \"lib.lust\"))";
+        let (root, errs) = read(src);
+        assert!(errs.is_empty());
+        let (root, errs) = Expander::new().expand(root.unwrap(), Some(&main));
+        fs::remove_dir_all(&dir).ok();
+        assert_eq!(
+            root.to_string(),
+            "(def x 1)\n(do (def y 2) (quote (include \"lib.lust\")))\n"
+        );
+        assert_eq!(errs.len(), 1);
+        assert!(matches!(
+            errs[0].kind(),
+            ExpandErrorKind::RecursiveInclude(_)
+        ));
+    }
+
+    #[test]
+    fn include_through_parent() {
+        let dir = env::temp_dir().join(format!("lust-include-parent-{}", std::process::id()));
+        fs::create_dir_all(dir.join("d")).unwrap();
+        fs::write(dir.join("d/self.lust"), "(include \"../d/self.lust\")").unwrap();
+        let (root, _) = read("(include \"d/self.lust\")");
+        let (_, errs) = Expander::new().expand(root.unwrap(), Some(&dir.join("main.lust")));
+        fs::remove_dir_all(&dir).ok();
+        assert_eq!(errs.len(), 1);
+        assert!(matches!(
+            errs[0].kind(),
+            ExpandErrorKind::RecursiveInclude(_)
+        ));
+    }
+
+    #[test]
+    fn destructure_let() {
+        let (root, _) = read("(let (((x y) pair) (z 1) ((a) b)) (+ x z))");
+        let (root, errs) = Expander::new().expand(root.unwrap(), None);
+        assert!(errs.is_empty());
+        assert_eq!(
+            root.to_string(),
+            "(let ((|#0| pair) (z 1) (|#1| b)) (match |#0| ((x y) (match |#1| ((a) (+ x z))))))\n"
+        );
+    }
+
+    #[test]
+    fn destructure_def() {
+        let expand = |src| {
+            let (root, _) = read(src);
+            let (root, errs) = Expander::new().expand(root.unwrap(), None);
+            (root.to_string(), errs.len())
+        };
+        assert_eq!(
+            expand("(def ((a _) . rest) xs)"),
+            (
+                "(def |#0| xs)\n(def a (match |#0| (((a _) . rest) a)))\n(def rest (match |#0| (((a _) . rest) rest)))\n"
+                    .to_string(),
+                0
+            )
+        );
+        assert_eq!(
+            expand("(def ((x y) z) p)"),
+            (
+                "(def |#0| p)\n(def x (match |#0| (((x y) z) x)))\n(def y (match |#0| (((x y) z) y)))\n(def z (match |#0| (((x y) z) z)))\n"
+                    .to_string(),
+                0
+            )
+        );
+        // functions, not patterns
+        assert_eq!(expand("(def (f x) x)"), ("(def (f x) x)\n".to_string(), 0));
+        assert_eq!(
+            expand("(def (f x . rest) (pair x rest))"),
+            ("(def (f x . rest) (pair x rest))\n".to_string(), 0)
+        );
+        assert_eq!(expand("(def ((a) . b) xs ys)").1, 1);
+    }
+
+    #[test]
+    fn threading() {
+        let expand = |src| {
+            let (root, _) = read(src);
+            let (root, errs) = Expander::new().expand(root.unwrap(), None);
+            assert!(errs.is_empty(), "{:?}", errs);
+            root.to_string()
+        };
+        assert_eq!(expand("(-> x (f a) g)"), "(g (f x a))\n");
+        assert_eq!(expand("(->> x (f a) g)"), "(g (f a x))\n");
+        assert_eq!(
+            expand("(as-> x n (f n) (g n))"),
+            "(let ((n x)) (let ((n (f n))) (g n)))\n"
+        );
+        assert_eq!(
+            expand("(doto x (f a) (g b))"),
+            "(let ((|#0| x)) (f |#0| a) (g |#0| b) |#0|)\n"
+        );
+        assert_eq!(expand("(: f (-> Int Int))"), "(: f (-> Int Int))\n");
+        assert_eq!(
+            expand("(def (apply (f : (-> Int Int)) x) (-> x f))"),
+            "(def (apply (f : (-> Int Int)) x) (f x))\n"
+        );
+    }
+
+    #[test]
+    fn comprehension() {
+        let expand = |src| {
+            let (root, _) = read(src);
+            let (root, errs) = Expander::new().expand(root.unwrap(), None);
+            (root.to_string(), errs.len())
+        };
+        assert_eq!(
+            expand("(for ((x xs) #:when (odd? x) (y ys)) (f x y))"),
+            (
+                "(let |#5| ((|#6| (let ((|#0| ())) (let |#1| ((|#2| xs) (|#0| |#0|)) (if (empty? |#2|) |#0| (|#1| (tail |#2|) (let ((x (head |#2|))) (if (odd? x) (let |#3| ((|#4| ys) (|#0| |#0|)) (if (empty? |#4|) |#0| (|#3| (tail |#4|) (let ((y (head |#4|))) (pair (f x y) |#0|))))) |#0|))))))) (|#0| ())) (if (empty? |#6|) |#0| (|#5| (tail |#6|) (pair (head |#6|) |#0|))))\n"
+                    .to_string(),
+                0
+            )
+        );
+        assert_eq!(expand("(for ((x xs) #:when) x)").1, 1);
+        assert_eq!(expand("(for (x) x)").1, 1);
+    }
+
+    #[test]
+    fn step() {
+        let (root, _) = read("(g (-> x f) (->> y (h z)))");
+        let mut sexpr = root.unwrap().sexprs.remove(0);
+        let mut expander = Expander::new();
+        let mut steps = vec![];
+        while let Some(result) = expander.expand_step(&sexpr) {
+            sexpr = result.unwrap();
+            steps.push(sexpr.to_string());
+        }
+        assert_eq!(steps, vec!["(g (f x) (->> y (h z)))", "(g (f x) (h z y))"]);
+    }
+
+    #[test]
+    fn invalid_syntax() {
+        let errors = |src| {
+            let (root, _) = read(src);
+            let (_, errs) = Expander::new().expand(root.unwrap(), None);
+            errs.into_iter()
+                .map(|err| err.to_string())
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(
+            errors("(let ((x 1) (y)) x)"),
+            ["12..15: let binding must be a (name value) pair"]
+        );
+        assert_eq!(
+            errors("(let x 1)"),
+            [
+                "7..8: let bindings must be a list of (name value) pairs",
+                "0..9: let needs a body"
+            ]
+        );
+        assert_eq!(
+            errors("(fn (x 1) x)"),
+            ["7..8: fn parameter must be a name or pattern"]
+        );
+        assert_eq!(
+            errors("(fn (x))"),
+            ["0..8: fn needs a parameter list and a body"]
+        );
+        assert_eq!(
+            errors("(cond x (y 1))"),
+            ["6..7: cond clause must be a (test body...) list"]
+        );
+        assert_eq!(
+            errors("(fn (x . 1) x)"),
+            ["9..10: fn rest parameter must be a name"]
+        );
+        assert_eq!(
+            errors("(fn (1 . xs) xs)"),
+            ["5..6: fn parameter must be a name or pattern"]
+        );
+        assert!(errors("(fn xs xs) (let loop ((i 0)) (loop i)) (def (f x) x)").is_empty());
+        assert!(errors("(fn (x . rest) x) (fn ((a b) . rest) a)").is_empty());
+    }
+}
@@ -1,3 +1,5 @@
 pub mod expand;
 pub mod parse;
 pub mod read;
+
+pub use read::{lex, parse_datum};
@@ -1,7 +1,9 @@
+#[cfg(feature = "bignum")]
+use lust_utils::num::{BigInt, BigRational};
 use lust_utils::{
     intern::InternedString,
     list::List,
-    num::{BigInt, BigRational, Int, Rational, Real},
+    num::{Complex, Int, Rational, Real},
     span::Span,
 };
 
@@ -73,11 +75,15 @@ pub enum PatternKind {
 #[derive(Debug, Clone, PartialEq)]
 pub enum Lit {
     Int(Int),
+    #[cfg(feature = "bignum")]
     BigInt(BigInt),
     Real(Real),
     Rational(Rational),
+    #[cfg(feature = "bignum")]
     BigRational(BigRational),
+    Complex(Complex),
     String(InternedString),
     Bool(bool),
     Char(char),
+    Bytes(Vec<u8>),
 }
@@ -0,0 +1,264 @@
+/*
+ * Generates large pseudo-random Lust sources for benchmarks and fuzzing.
+ * The output only depends on the options, so a seed is enough to get the
+ * same input again, and everything generated reads without errors.
+ *
+ *     let src = corpus::generate(&CorpusOptions {
+ *         seed: 7,
+ *         size: 1 << 20,
+ *         ..Default::default()
+ *     });
+ */
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct CorpusOptions {
+    pub seed: u64,
+    // stop after the first top level datum that reaches this many bytes
+    pub size: usize,
+    // lists deeper than this only hold atoms
+    pub max_depth: usize,
+    // the most items in one list
+    pub max_width: usize,
+    pub mix: AtomMix,
+}
+
+impl Default for CorpusOptions {
+    fn default() -> Self {
+        Self {
+            seed: 0,
+            size: 64 * 1024,
+            max_depth: 8,
+            max_width: 8,
+            mix: AtomMix::default(),
+        }
+    }
+}
+
+// Relative weights of each kind of atom, a weight of 0 leaves it out.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct AtomMix {
+    pub symbols: u32,
+    pub keywords: u32,
+    pub ints: u32,
+    pub reals: u32,
+    pub strings: u32,
+    pub chars: u32,
+}
+
+impl Default for AtomMix {
+    fn default() -> Self {
+        Self {
+            symbols: 8,
+            keywords: 1,
+            ints: 4,
+            reals: 1,
+            strings: 2,
+            chars: 1,
+        }
+    }
+}
+
+pub fn generate(opts: &CorpusOptions) -> String {
+    let mut gen = Generator {
+        rng: SplitMix(opts.seed),
+        opts,
+        out: String::with_capacity(opts.size),
+    };
+    while gen.out.len() < opts.size {
+        gen.datum(0);
+        gen.out.push('\n');
+    }
+    gen.out
+}
+
+const SYMBOLS: &[&str] = &[
+    "x",
+    "acc",
+    "head",
+    "tail",
+    "map",
+    "filter",
+    "fold",
+    "def",
+    "let",
+    "fn",
+    "if",
+    "empty?",
+    "pair",
+    "+",
+    "-",
+    "*",
+    "<=",
+    "string-append",
+    "make-hash-table",
+];
+
+const CHARS: &[&str] = &["a", "z", "0", "(", "space", "newline", "x3bb"];
+
+struct Generator<'a> {
+    rng: SplitMix,
+    opts: &'a CorpusOptions,
+    out: String,
+}
+
+impl Generator<'_> {
+    fn datum(&mut self, depth: usize) {
+        if depth >= self.opts.max_depth || self.rng.below(3) == 0 {
+            self.atom();
+            return;
+        }
+        match self.rng.below(8) {
+            0 => {
+                self.out.push('\'');
+                self.datum(depth + 1);
+            }
+            1 => self.list("#[", "]", depth),
+            _ => self.list("(", ")", depth),
+        }
+    }
+
+    fn list(&mut self, open: &str, close: &str, depth: usize) {
+        self.out.push_str(open);
+        let width = self.rng.below(self.opts.max_width + 1);
+        for i in 0..width {
+            if i != 0 {
+                self.out.push(' ');
+            }
+            self.datum(depth + 1);
+        }
+        self.out.push_str(close);
+    }
+
+    fn atom(&mut self) {
+        let mix = self.opts.mix;
+        let weights = [
+            mix.symbols,
+            mix.keywords,
+            mix.ints,
+            mix.reals,
+            mix.strings,
+            mix.chars,
+        ];
+        let total = weights.iter().sum::<u32>() as usize;
+        // with every weight at 0 there's nothing to pick, so use a symbol
+        let mut pick = if total == 0 {
+            0
+        } else {
+            self.rng.below(total) as u32
+        };
+        let kind = weights
+            .iter()
+            .position(|&w| {
+                if pick < w {
+                    return true;
+                }
+                pick -= w;
+                false
+            })
+            .unwrap_or(0);
+        let atom = match kind {
+            0 => self.symbol(),
+            1 => format!(":{}", self.symbol()),
+            2 => (self.rng.next() as i32 / 1000).to_string(),
+            3 => format!("{}.{}", self.rng.below(1000), self.rng.below(100)),
+            4 => self.string(),
+            _ => format!("#\\{}", CHARS[self.rng.below(CHARS.len())]),
+        };
+        self.out.push_str(&atom);
+    }
+
+    fn symbol(&mut self) -> String {
+        let name = SYMBOLS[self.rng.below(SYMBOLS.len())];
+        match self.rng.below(4) {
+            0 => format!("{}-{}", name, self.rng.below(100)),
+            _ => name.to_string(),
+        }
+    }
+
+    fn string(&mut self) -> String {
+        let mut s = String::from("\"");
+        for _ in 0..self.rng.below(24) {
+            match self.rng.below(16) {
+                0 => s.push_str("\\n"),
+                1 => s.push_str("\\\""),
+                2 => s.push(' '),
+                n => s.push((b'a' + n as u8) as char),
+            }
+        }
+        s.push('"');
+        s
+    }
+}
+
+// SplitMix64, small and good enough for test inputs
+struct SplitMix(u64);
+
+impl SplitMix {
+    fn next(&mut self) -> u64 {
+        self.0 = self.0.wrapping_add(0x9e3779b97f4a7c15);
+        let mut z = self.0;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58476d1ce4e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d049bb133111eb);
+        z ^ (z >> 31)
+    }
+
+    fn below(&mut self, n: usize) -> usize {
+        (self.next() % n as u64) as usize
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{generate, AtomMix, CorpusOptions};
+    use crate::read::read;
+
+    #[test]
+    fn seeded() {
+        let opts = CorpusOptions {
+            seed: 42,
+            size: 4096,
+            ..Default::default()
+        };
+        assert_eq!(generate(&opts), generate(&opts));
+        assert_ne!(
+            generate(&opts),
+            generate(&CorpusOptions {
+                seed: 43,
+                ..opts.clone()
+            })
+        );
+        assert!(generate(&opts).len() >= 4096);
+    }
+
+    #[test]
+    fn reads_cleanly() {
+        for seed in 0..8 {
+            let src = generate(&CorpusOptions {
+                seed,
+                size: 8192,
+                ..Default::default()
+            });
+            let (root, errs) = read(&src);
+            assert!(errs.is_empty(), "seed {}: {:?}", seed, errs);
+            assert!(!root.unwrap().sexprs.is_empty());
+        }
+    }
+
+    #[test]
+    fn atom_mix() {
+        let src = generate(&CorpusOptions {
+            mix: AtomMix {
+                symbols: 0,
+                keywords: 0,
+                ints: 1,
+                reals: 0,
+                strings: 0,
+                chars: 0,
+            },
+            ..Default::default()
+        });
+        assert!(src
+            .chars()
+            .all(|c| c.is_ascii_digit() || "-()#[]' \n".contains(c)));
+    }
+}
@@ -0,0 +1,306 @@
+/*
+ * Data as a graph of pairs, for datum labels. A sexpr is an owned tree, so
+ * the reader leaves `#0=` and `#0#` in it as markers. `Datum::from_sexpr`
+ * resolves them into pairs that really are shared, or cyclic, and writing
+ * a datum labels every pair it reaches more than once, so
+ *
+ *     #0=(a . #0#)
+ *
+ * reads as a one pair cycle and writes back the same way. Pairs are
+ * reference counted, so a cycle is never freed.
+ */
+use super::{
+    error::SyntaxError,
+    sexpr::{AtomKind, Sexpr, SexprKind},
+};
+use lust_utils::list::List;
+use std::{
+    cell::RefCell,
+    collections::{HashMap, HashSet},
+    fmt::{Debug, Display},
+    rc::Rc,
+};
+
+#[derive(Clone, Default)]
+pub enum Datum {
+    Atom(AtomKind),
+    #[default]
+    Nil,
+    Pair(Rc<Pair>),
+}
+
+pub struct Pair {
+    pub head: RefCell<Datum>,
+    pub tail: RefCell<Datum>,
+}
+
+impl Pair {
+    pub fn new(head: Datum, tail: Datum) -> Rc<Self> {
+        Rc::new(Self {
+            head: RefCell::new(head),
+            tail: RefCell::new(tail),
+        })
+    }
+}
+
+// Dropped from an explicit stack, like Sexpr, so a long list can't
+// overflow. A pair still referenced elsewhere is left to its other owners.
+impl Drop for Pair {
+    fn drop(&mut self) {
+        let mut stack = vec![self.head.take(), self.tail.take()];
+        while let Some(datum) = stack.pop() {
+            if let Datum::Pair(pair) = datum {
+                if let Ok(pair) = Rc::try_unwrap(pair) {
+                    stack.push(pair.head.take());
+                    stack.push(pair.tail.take());
+                }
+            }
+        }
+    }
+}
+
+#[derive(Clone, Copy)]
+enum Field {
+    Head,
+    Tail,
+}
+
+impl Datum {
+    pub fn cons(head: Datum, tail: Datum) -> Self {
+        Datum::Pair(Pair::new(head, tail))
+    }
+
+    // the same pair, not just an equal one, like eq?
+    pub fn is(&self, other: &Datum) -> bool {
+        match (self, other) {
+            (Datum::Pair(a), Datum::Pair(b)) => Rc::ptr_eq(a, b),
+            _ => false,
+        }
+    }
+
+    pub fn head(&self) -> Option<Datum> {
+        match self {
+            Datum::Pair(pair) => Some(pair.head.borrow().clone()),
+            _ => None,
+        }
+    }
+
+    pub fn tail(&self) -> Option<Datum> {
+        match self {
+            Datum::Pair(pair) => Some(pair.tail.borrow().clone()),
+            _ => None,
+        }
+    }
+
+    // Labels are scoped to the datum, as the reader checks them. Each
+    // datum is built into a field of a pair that's already there, the root
+    // into the head of a scratch one, so a label can be bound to a list's
+    // first pair before its items are built.
+    pub fn from_sexpr(sexpr: &Sexpr) -> Result<Self, SyntaxError> {
+        let root = Pair::new(Datum::Nil, Datum::Nil);
+        let mut labels = HashMap::new();
+        let mut stack = vec![(sexpr, root.clone(), Field::Head)];
+        while let Some((mut sexpr, pair, field)) = stack.pop() {
+            let mut names = vec![];
+            while let SexprKind::DatumLabel(n, datum) = sexpr.kind.as_ref() {
+                if labels.contains_key(n) || names.contains(n) {
+                    return Err(SyntaxError::DuplicateLabel(*n, sexpr.span));
+                }
+                names.push(*n);
+                sexpr = datum;
+            }
+            let datum = match sexpr.kind.as_ref() {
+                SexprKind::Atom(atom) => Datum::Atom(atom.kind.as_ref().clone()),
+                SexprKind::List(items) => spine(items, None, &mut stack),
+                SexprKind::DottedList(items, tail) => spine(items, Some(tail), &mut stack),
+                // `#0=#0#` is caught here too, the label isn't bound yet
+                SexprKind::DatumRef(n) => match labels.get(n) {
+                    Some(datum) => Datum::clone(datum),
+                    None => return Err(SyntaxError::UndefinedLabel(*n, sexpr.span)),
+                },
+                SexprKind::DatumLabel(..) => unreachable!(),
+            };
+            for n in names {
+                labels.insert(n, datum.clone());
+            }
+            match field {
+                Field::Head => *pair.head.borrow_mut() = datum,
+                Field::Tail => *pair.tail.borrow_mut() = datum,
+            }
+        }
+        Ok(root.head.take())
+    }
+}
+
+// The pairs of a list, linked up with their heads left to build. They're
+// pushed so the items come off the stack in source order, then the tail.
+fn spine<'a>(
+    items: &'a List<Sexpr>,
+    tail: Option<&'a Sexpr>,
+    stack: &mut Vec<(&'a Sexpr, Rc<Pair>, Field)>,
+) -> Datum {
+    let items = items.iter().collect::<Vec<_>>();
+    let pairs = items
+        .iter()
+        .map(|_| Pair::new(Datum::Nil, Datum::Nil))
+        .collect::<Vec<_>>();
+    for link in pairs.windows(2) {
+        *link[0].tail.borrow_mut() = Datum::Pair(link[1].clone());
+    }
+    if let (Some(tail), Some(last)) = (tail, pairs.last()) {
+        stack.push((tail, last.clone(), Field::Tail));
+    }
+    for (item, pair) in items.iter().zip(&pairs).rev() {
+        stack.push((item, pair.clone(), Field::Head));
+    }
+    pairs
+        .first()
+        .map_or(Datum::Nil, |pair| Datum::Pair(pair.clone()))
+}
+
+// Written in two passes, both from explicit stacks. The first finds the
+// pairs reached more than once, the second writes the datum, numbering
+// those pairs in the order they're written: `#n=` the first time and `#n#`
+// after. A shared pair in the tail of a list is written after a dot, so
+// its label has somewhere to go.
+impl Display for Datum {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let mut seen = HashSet::new();
+        let mut shared = HashSet::new();
+        let mut stack = vec![self.clone()];
+        while let Some(datum) = stack.pop() {
+            if let Datum::Pair(pair) = datum {
+                if !seen.insert(Rc::as_ptr(&pair)) {
+                    shared.insert(Rc::as_ptr(&pair));
+                    continue;
+                }
+                stack.push(pair.tail.borrow().clone());
+                stack.push(pair.head.borrow().clone());
+            }
+        }
+
+        enum Item {
+            Datum(Datum),
+            // what's left of a list after the items written so far
+            Rest(Datum),
+            Text(&'static str),
+        }
+        let mut labels = HashMap::new();
+        let mut stack = vec![Item::Datum(self.clone())];
+        while let Some(item) = stack.pop() {
+            match item {
+                Item::Text(text) => f.write_str(text)?,
+                Item::Datum(Datum::Atom(atom)) => write!(f, "{}", atom)?,
+                Item::Datum(Datum::Nil) => f.write_str("()")?,
+                Item::Datum(Datum::Pair(pair)) => {
+                    let ptr = Rc::as_ptr(&pair);
+                    if shared.contains(&ptr) {
+                        if let Some(n) = labels.get(&ptr) {
+                            write!(f, "#{}#", n)?;
+                            continue;
+                        }
+                        let n = labels.len();
+                        labels.insert(ptr, n);
+                        write!(f, "#{}=", n)?;
+                    }
+                    f.write_str("(")?;
+                    stack.push(Item::Rest(pair.tail.borrow().clone()));
+                    stack.push(Item::Datum(pair.head.borrow().clone()));
+                }
+                Item::Rest(Datum::Nil) => f.write_str(")")?,
+                Item::Rest(Datum::Pair(pair)) if !shared.contains(&Rc::as_ptr(&pair)) => {
+                    f.write_str(" ")?;
+                    stack.push(Item::Rest(pair.tail.borrow().clone()));
+                    stack.push(Item::Datum(pair.head.borrow().clone()));
+                }
+                Item::Rest(tail) => {
+                    f.write_str(" . ")?;
+                    stack.push(Item::Text(")"));
+                    stack.push(Item::Datum(tail));
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+// written out, since a cycle would send a derived Debug round it forever
+impl Debug for Datum {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Datum({})", self)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Datum;
+    use crate::read::{error::SyntaxError, read, sexpr::AtomKind};
+    use lust_utils::{intern::InternedString, span::Span};
+
+    fn datum(src: &str) -> Result<Datum, SyntaxError> {
+        // the reader's own label errors aren't looked at, these are
+        // found again
+        Datum::from_sexpr(&read(src).0.unwrap().sexprs[0])
+    }
+
+    #[test]
+    fn cycle() {
+        let d = datum("#0=(1 . #0#)").unwrap();
+        assert!(d.tail().unwrap().is(&d));
+        assert_eq!(d.to_string(), "#0=(1 . #0#)");
+        let d = datum("#0=(a b #0#)").unwrap();
+        let third = d.tail().unwrap().tail().unwrap().head().unwrap();
+        assert!(third.is(&d));
+        assert_eq!(d.to_string(), "#0=(a b #0#)");
+    }
+
+    #[test]
+    fn sharing() {
+        let d = datum("(#0=(a) #0# (a))").unwrap();
+        let first = d.head().unwrap();
+        let second = d.tail().unwrap().head().unwrap();
+        let third = d.tail().unwrap().tail().unwrap().head().unwrap();
+        assert!(first.is(&second));
+        assert!(!first.is(&third));
+        assert_eq!(d.to_string(), "(#0=(a) #0# (a))");
+        // sharing is found whether or not it was read from labels, and
+        // the labels are renumbered in the order they're written
+        let d = datum("(#5=(x . #3=(y)) #3# #5#)").unwrap();
+        assert_eq!(d.to_string(), "(#0=(x . #1=(y)) #1# #0#)");
+        let shared = Datum::cons(
+            Datum::Atom(AtomKind::Sym(InternedString::from("b"))),
+            Datum::Nil,
+        );
+        let d = Datum::cons(shared.clone(), shared);
+        assert_eq!(d.to_string(), "(#0=(b) . #0#)");
+    }
+
+    #[test]
+    fn trees() {
+        for src in ["()", "x", "(a (b c) . d)", "#0=1", "(#0=a #0#)"] {
+            let d = datum(src).unwrap();
+            assert_eq!(d.to_string(), src.replace("#0=", "").replace("#0#", "a"));
+        }
+    }
+
+    #[test]
+    fn bad_labels() {
+        assert_eq!(
+            datum("#0=#0#").unwrap_err(),
+            SyntaxError::UndefinedLabel(0, Span::new(3, 6))
+        );
+        assert_eq!(
+            datum("(#1# #1=a)").unwrap_err(),
+            SyntaxError::UndefinedLabel(1, Span::new(1, 4))
+        );
+    }
+
+    #[test]
+    fn long_list() {
+        let src = format!("#0=({} . #0#)", "x ".repeat(100_000));
+        let d = datum(&src).unwrap();
+        assert_eq!(d.to_string().len(), src.len() - 1);
+        let d = datum(&format!("({})", "x ".repeat(100_000))).unwrap();
+        drop(d);
+    }
+}
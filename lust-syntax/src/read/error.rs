@@ -0,0 +1,85 @@
+use lust_utils::span::{FileId, Span};
+use std::fmt::Display;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum SyntaxError {
+    LexError(Span),
+    ParseError(String, Span),
+    // the span of the opening #|
+    UnterminatedComment(Span),
+    // the span of the whole escape sequence
+    InvalidEscape(Span),
+    IntOverflow(Span),
+    // the span of the #N# or of the second #N= datum
+    UndefinedLabel(u32, Span),
+    DuplicateLabel(u32, Span),
+    // the span of the invalid bytes
+    InvalidUtf8(Span),
+    // what was too long and the limit in bytes
+    TooLong(&'static str, usize, Span),
+}
+
+impl SyntaxError {
+    pub fn span(&self) -> Span {
+        match self {
+            SyntaxError::LexError(span) => *span,
+            SyntaxError::ParseError(_, span) => *span,
+            SyntaxError::UnterminatedComment(span) => *span,
+            SyntaxError::InvalidEscape(span) => *span,
+            SyntaxError::IntOverflow(span) => *span,
+            SyntaxError::UndefinedLabel(_, span) => *span,
+            SyntaxError::DuplicateLabel(_, span) => *span,
+            SyntaxError::InvalidUtf8(span) => *span,
+            SyntaxError::TooLong(_, _, span) => *span,
+        }
+    }
+
+    // the same error in a source that starts `offset` bytes earlier
+    pub fn shift(self, offset: u32) -> Self {
+        self.map_span(|span| span.shift(offset))
+    }
+
+    pub fn in_file(self, file: FileId) -> Self {
+        self.map_span(|span| span.in_file(file))
+    }
+
+    fn map_span(self, f: impl Fn(Span) -> Span) -> Self {
+        match self {
+            SyntaxError::LexError(span) => SyntaxError::LexError(f(span)),
+            SyntaxError::ParseError(msg, span) => SyntaxError::ParseError(msg, f(span)),
+            SyntaxError::UnterminatedComment(span) => SyntaxError::UnterminatedComment(f(span)),
+            SyntaxError::InvalidEscape(span) => SyntaxError::InvalidEscape(f(span)),
+            SyntaxError::IntOverflow(span) => SyntaxError::IntOverflow(f(span)),
+            SyntaxError::UndefinedLabel(n, span) => SyntaxError::UndefinedLabel(n, f(span)),
+            SyntaxError::DuplicateLabel(n, span) => SyntaxError::DuplicateLabel(n, f(span)),
+            SyntaxError::InvalidUtf8(span) => SyntaxError::InvalidUtf8(f(span)),
+            SyntaxError::TooLong(what, limit, span) => SyntaxError::TooLong(what, limit, f(span)),
+        }
+    }
+}
+
+impl Display for SyntaxError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            SyntaxError::LexError(span) => write!(f, "{}: unexpected character", span),
+            SyntaxError::ParseError(msg, span) => write!(f, "{}: {}", span, msg),
+            SyntaxError::UnterminatedComment(span) => {
+                write!(f, "{}: unterminated block comment", span)
+            }
+            SyntaxError::InvalidEscape(span) => write!(f, "{}: invalid escape sequence", span),
+            SyntaxError::IntOverflow(span) => {
+                write!(f, "{}: integer literal doesn't fit in 64 bits", span)
+            }
+            SyntaxError::UndefinedLabel(n, span) => write!(f, "{}: undefined label #{}#", span, n),
+            SyntaxError::DuplicateLabel(n, span) => {
+                write!(f, "{}: label #{}= is already defined", span, n)
+            }
+            SyntaxError::InvalidUtf8(span) => write!(f, "{}: invalid UTF-8", span),
+            SyntaxError::TooLong(what, limit, span) => {
+                write!(f, "{}: {} longer than {} bytes", span, what, limit)
+            }
+        }
+    }
+}
+
+pub type SyntaxResult<T> = std::result::Result<T, SyntaxError>;
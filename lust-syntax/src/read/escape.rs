@@ -0,0 +1,185 @@
+/*
+ * Escape sequences in string literals. The reader decodes them once, after
+ * lexing, so `Lit::String` holds the string's actual contents, and the
+ * printer escapes them again. The literal as written is still there in the
+ * source, see `Atom::source`.
+ *
+ *     \n \t \r \a \b \0 \\ \"   the usual characters
+ *     \x41;                     a character by its hex code point
+ *     \u{41}                    the same, Rust style
+ *     \<newline>                a line continuation, which also skips the
+ *                               next line's indentation
+ */
+use super::error::SyntaxError;
+use lust_utils::span::Span;
+use std::{iter::Peekable, str::CharIndices};
+
+// `src` is the text between the quotes and `offset` where it starts in the
+// source.
+pub fn unescape(src: &str, offset: u32) -> Result<String, SyntaxError> {
+    let mut out = String::with_capacity(src.len());
+    let mut chars = src.char_indices().peekable();
+    while let Some((start, c)) = chars.next() {
+        if c != '\\' {
+            out.push(c);
+            continue;
+        }
+        let decoded = match chars.next() {
+            Some((_, 'n')) => Some('\n'),
+            Some((_, 't')) => Some('\t'),
+            Some((_, 'r')) => Some('\r'),
+            Some((_, 'a')) => Some('\u{7}'),
+            Some((_, 'b')) => Some('\u{8}'),
+            Some((_, '0')) => Some('\0'),
+            Some((_, '\\')) => Some('\\'),
+            Some((_, '"')) => Some('"'),
+            Some((_, 'x')) => hex(&mut chars, ';'),
+            Some((_, 'u')) => chars
+                .next_if(|(_, c)| *c == '{')
+                .and_then(|_| hex(&mut chars, '}')),
+            Some((_, c)) if c.is_whitespace() && continuation(&mut chars, c) => continue,
+            _ => None,
+        };
+        match decoded {
+            Some(c) => out.push(c),
+            None => {
+                let end = chars.peek().map_or(src.len(), |(i, _)| *i);
+                return Err(SyntaxError::InvalidEscape(Span::from(
+                    offset + start as u32..offset + end as u32,
+                )));
+            }
+        }
+    }
+    Ok(out)
+}
+
+// hex digits up to `close`
+fn hex(chars: &mut Peekable<CharIndices>, close: char) -> Option<char> {
+    let mut code = 0u32;
+    let mut digits = 0;
+    loop {
+        match chars.next()? {
+            (_, c) if c == close && digits > 0 => return char::from_u32(code),
+            (_, c) => {
+                code = code.checked_mul(16)?.checked_add(c.to_digit(16)?)?;
+                digits += 1;
+            }
+        }
+    }
+}
+
+// Skips the rest of a line continuation whose first whitespace character
+// is `first`. There can't be anything but whitespace before the newline.
+fn continuation(chars: &mut Peekable<CharIndices>, first: char) -> bool {
+    let mut newline = first == '\n';
+    while !newline {
+        match chars.next_if(|(_, c)| c.is_whitespace()) {
+            Some((_, c)) => newline = c == '\n',
+            None => return false,
+        }
+    }
+    while chars.next_if(|(_, c)| *c == ' ' || *c == '\t').is_some() {}
+    true
+}
+
+// the inverse of unescape, without the quotes
+pub fn escape(s: &str) -> String {
+    let mut out = String::with_capacity(s.len());
+    for c in s.chars() {
+        match c {
+            '\n' => out.push_str("\\n"),
+            '\t' => out.push_str("\\t"),
+            '\r' => out.push_str("\\r"),
+            '\\' => out.push_str("\\\\"),
+            '"' => out.push_str("\\\""),
+            c if c.is_control() => out.push_str(&format!("\\x{:x};", c as u32)),
+            c => out.push(c),
+        }
+    }
+    out
+}
+
+// Symbols that wouldn't read back as themselves are printed between pipes,
+// as in |hello world|, with pipes and backslashes escaped.
+pub fn escape_symbol(name: &str) -> String {
+    // +i and -i are the imaginary unit
+    let numeric = matches!(name, "+i" | "-i")
+        || name
+            .strip_prefix(['+', '-'])
+            .unwrap_or(name)
+            .starts_with(|c: char| c.is_ascii_digit());
+    // a lone : is a symbol, but :name is a keyword and # starts other syntax
+    let prefixed = name.starts_with('#') || (name.starts_with(':') && name != ":");
+    let special = |c: char| c.is_whitespace() || "()[]{}\",;'`|\\.".contains(c);
+    if !name.is_empty() && !numeric && !prefixed && !name.contains(special) {
+        return name.to_string();
+    }
+    let mut out = String::from("|");
+    for c in name.chars() {
+        if c == '|' || c == '\\' {
+            out.push('\\');
+        }
+        out.push(c);
+    }
+    out.push('|');
+    out
+}
+
+// the text between the pipes of a |...| symbol
+pub fn unescape_symbol(src: &str) -> String {
+    let mut out = String::with_capacity(src.len());
+    let mut chars = src.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' => out.extend(chars.next()),
+            c => out.push(c),
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{escape, escape_symbol, unescape, unescape_symbol};
+    use crate::read::error::SyntaxError;
+    use lust_utils::span::Span;
+
+    #[test]
+    fn escapes() {
+        let src = r#"a\n\t\\\"\x41;\u{3bb}\x7;b\
+            c"#;
+        let s = unescape(src, 0).unwrap();
+        assert_eq!(s, "a\n\t\\\"A\u{3bb}\u{7}bc");
+        assert_eq!(unescape(&escape(&s), 0).unwrap(), s);
+        assert_eq!(escape(&s), r#"a\n\t\\\"Aλ\x7;bc"#);
+    }
+
+    #[test]
+    fn invalid() {
+        let err = |src: &str| match unescape(src, 10) {
+            Err(SyntaxError::InvalidEscape(span)) => span,
+            other => panic!("expected an invalid escape in {:?}, got {:?}", src, other),
+        };
+        assert_eq!(err(r"ab\q"), Span::from(12..14u32));
+        assert_eq!(err(r"\x41 "), Span::from(10..15u32));
+        assert_eq!(err(r"\u{110000}"), Span::from(10..20u32));
+        assert_eq!(err(r"\u41"), Span::from(10..12u32));
+        assert_eq!(err(r"a\ b"), Span::from(11..13u32));
+    }
+
+    #[test]
+    fn symbols() {
+        assert_eq!(escape_symbol("abc"), "abc");
+        assert_eq!(escape_symbol("-"), "-");
+        assert_eq!(escape_symbol("#0"), "|#0|");
+        assert_eq!(escape_symbol(":"), ":");
+        assert_eq!(escape_symbol(":k"), "|:k|");
+        assert_eq!(escape_symbol("+i"), "|+i|");
+        assert_eq!(escape_symbol("-in"), "-in");
+        assert_eq!(escape_symbol(""), "||");
+        assert_eq!(escape_symbol("-42"), "|-42|");
+        assert_eq!(escape_symbol("a.b"), "|a.b|");
+        assert_eq!(escape_symbol(r"a|b\c d"), r"|a\|b\\c d|");
+        assert_eq!(unescape_symbol(r"a\|b\\c d"), r"a|b\c d");
+    }
+}
@@ -0,0 +1,191 @@
+/*
+ * Interpolated strings. `#"Hello ~(name), you have ~(count n) items"`
+ * reads as
+ *
+ *     (format "Hello ~a, you have ~a items" name (count n))
+ *
+ * The parens after a `~` hold an expression: a single form is used as is,
+ * several forms are a call. A literal tilde is written `\~`. Embedded
+ * expressions keep their spans in the enclosing source, and are read with
+ * the same options as it, case folding included.
+ */
+use super::{
+    error::SyntaxError,
+    escape::unescape,
+    lexer_state,
+    options::ReaderOptions,
+    read_tokens_with,
+    sexpr::{Atom, AtomKind, Lit, Sexpr, SexprKind},
+    source::TokenSource,
+    token::{LexerState, Token},
+};
+use logos::Logos;
+use lust_utils::{intern::InternedString, list::List, span::Span};
+use std::ops::Range;
+
+// An interpolated string as lexed, the text between the quotes and
+// whether symbols were being folded to lower case where it was.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Interp {
+    pub raw: InternedString,
+    pub fold_case: bool,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Part {
+    // source text, escapes and all
+    Text(Range<usize>),
+    // \~
+    Tilde,
+    // the source between the parens of ~(...)
+    Expr(Range<usize>),
+}
+
+// Splits the body of an interpolated string, just after the opening #",
+// into parts up to the closing quote. Returns the parts and the length
+// consumed including the quote, or None if the string isn't terminated.
+pub fn split(src: &str) -> Option<(Vec<Part>, usize)> {
+    let mut parts = vec![];
+    // where the text since the last part began
+    let mut start = 0;
+    let text = |parts: &mut Vec<Part>, start: usize, end: usize| {
+        if start < end {
+            parts.push(Part::Text(start..end));
+        }
+    };
+    let mut chars = src.char_indices().peekable();
+    while let Some((i, c)) = chars.next() {
+        match c {
+            '"' => {
+                text(&mut parts, start, i);
+                return Some((parts, i + 1));
+            }
+            '\\' => {
+                if let (_, '~') = chars.next()? {
+                    text(&mut parts, start, i);
+                    parts.push(Part::Tilde);
+                    start = i + 2;
+                }
+            }
+            '~' if matches!(chars.peek(), Some((_, '('))) => {
+                let open = i + 2;
+                let end = open + expr_end(&src[open..])?;
+                while chars.next_if(|(j, _)| *j <= end).is_some() {}
+                text(&mut parts, start, i);
+                parts.push(Part::Expr(open..end));
+                start = end + 1;
+            }
+            _ => (),
+        }
+    }
+    None
+}
+
+// the index of the paren that closes an expression starting just after
+// an open paren
+fn expr_end(src: &str) -> Option<usize> {
+    let mut depth = 0;
+    let mut in_str = false;
+    let mut chars = src.char_indices();
+    while let Some((i, c)) = chars.next() {
+        match c {
+            '\\' if in_str => {
+                chars.next();
+            }
+            '"' => in_str = !in_str,
+            '(' if !in_str => depth += 1,
+            ')' if !in_str => {
+                if depth == 0 {
+                    return Some(i);
+                }
+                depth -= 1;
+            }
+            _ => (),
+        }
+    }
+    None
+}
+
+// `span` is the span of the whole token.
+pub fn desugar(interp: &Interp, span: Span, opts: &ReaderOptions) -> Result<Sexpr, SyntaxError> {
+    let raw = interp.raw.to_string();
+    let body = format!("{}\"", raw);
+    let Some((parts, _)) = split(&body) else {
+        return Err(SyntaxError::ParseError(
+            "unterminated interpolated string".to_string(),
+            span,
+        ));
+    };
+    // skip the #"
+    let offset = span.start() + 2;
+    let mut template = String::new();
+    let mut args = vec![];
+    for part in parts {
+        match part {
+            Part::Text(range) => {
+                let text = unescape(&raw[range.clone()], offset + range.start as u32)?;
+                template.push_str(&text.replace('~', "~~"))
+            }
+            Part::Tilde => template.push_str("~~"),
+            Part::Expr(range) => {
+                template.push_str("~a");
+                args.push(read_expr(
+                    &raw[range.clone()],
+                    offset + range.start as u32,
+                    interp.fold_case,
+                    opts,
+                )?);
+            }
+        }
+    }
+    let mut items = vec![
+        Sexpr::new(
+            SexprKind::Atom(Atom::new(
+                AtomKind::Sym(InternedString::from("format")),
+                Span::from(span.start()..span.start()),
+            )),
+            span,
+        ),
+        Sexpr::new(
+            SexprKind::Atom(Atom::new(
+                AtomKind::Lit(Lit::String(InternedString::from(template))),
+                span,
+            )),
+            span,
+        ),
+    ];
+    items.extend(args);
+    Ok(Sexpr::new(SexprKind::List(List::from(items)), span))
+}
+
+fn read_expr(
+    src: &str,
+    offset: u32,
+    fold_case: bool,
+    opts: &ReaderOptions,
+) -> Result<Sexpr, SyntaxError> {
+    let state = LexerState {
+        fold_case,
+        ..lexer_state(opts)
+    };
+    let mut lexer = Token::lexer_with_extras(src, state);
+    let mut tokens = vec![];
+    while let Some((tok, span)) = lexer.next_token() {
+        tokens.push((tok, Span::from(span.start() + offset..span.end() + offset)));
+    }
+    let span = Span::from(offset..offset + src.len() as u32);
+    let (root, errs) =
+        read_tokens_with(tokens.into_iter(), Span::from(span.end()..span.end()), opts);
+    if let Some(err) = errs.into_iter().next() {
+        return Err(err);
+    }
+    let mut sexprs = root.map(|root| root.sexprs).unwrap_or_default();
+    match sexprs.len() {
+        0 => Err(SyntaxError::ParseError(
+            "empty interpolation".to_string(),
+            span,
+        )),
+        1 => Ok(sexprs.remove(0)),
+        _ => Ok(Sexpr::new(SexprKind::List(List::from(sexprs)), span)),
+    }
+}
@@ -0,0 +1,54 @@
+/*
+ * Checks datum labels after a read. A label's scope is the top level datum
+ * it's in, and a `#N#` has to come after its `#N=`, though it can be inside
+ * the labelled datum itself, as in
+ *
+ *     #0=(a b . #0#)
+ *
+ * Sexprs are owned trees so they can't hold the cycle itself; the labels
+ * stay in the tree as markers and this only makes sure every reference has
+ * something to point to. `Datum::from_sexpr` builds the shared structure.
+ */
+use super::{
+    error::SyntaxError,
+    sexpr::{Root, Sexpr, SexprKind},
+};
+use std::collections::HashSet;
+
+pub fn check(root: &Root) -> Vec<SyntaxError> {
+    root.sexprs.iter().flat_map(check_datum).collect()
+}
+
+// Walks the datum in source order with an explicit stack, like Sexpr's
+// Drop, so deep data can't overflow.
+pub fn check_datum(sexpr: &Sexpr) -> Vec<SyntaxError> {
+    let mut errs = vec![];
+    let mut labels = HashSet::new();
+    let mut stack = vec![sexpr];
+    while let Some(sexpr) = stack.pop() {
+        match sexpr.kind.as_ref() {
+            SexprKind::Atom(_) => (),
+            SexprKind::List(list) => {
+                let items: Vec<_> = list.iter().collect();
+                stack.extend(items.into_iter().rev());
+            }
+            SexprKind::DottedList(list, tail) => {
+                stack.push(tail);
+                let items: Vec<_> = list.iter().collect();
+                stack.extend(items.into_iter().rev());
+            }
+            SexprKind::DatumLabel(n, datum) => {
+                if !labels.insert(*n) {
+                    errs.push(SyntaxError::DuplicateLabel(*n, sexpr.span));
+                }
+                stack.push(datum);
+            }
+            SexprKind::DatumRef(n) => {
+                if !labels.contains(n) {
+                    errs.push(SyntaxError::UndefinedLabel(*n, sexpr.span));
+                }
+            }
+        }
+    }
+    errs
+}
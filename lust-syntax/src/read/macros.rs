@@ -0,0 +1,72 @@
+/*
+ * Reader macros. An embedder can add their own literals by registering a
+ * name, then `#name datum` reads the datum as usual and hands it to the
+ * macro, which returns the sexpr to read in its place:
+ *
+ *     let mut opts = ReaderOptions::default();
+ *     opts.macros.insert("re", |ctx| Ok(ctx.list(vec![ctx.sym("regex"), ctx.datum.clone()])));
+ *
+ * reads `#re"a+"` as `(regex "a+")`. The name has to follow the `#`
+ * directly, and can't start like one of the built in `#` forms, so `#t`,
+ * `#f`, `#r"` and the like keep their meaning.
+ */
+use super::sexpr::{Atom, AtomKind, Sexpr, SexprKind};
+use lust_utils::{intern::InternedString, list::List, span::Span};
+use std::{collections::HashMap, fmt, rc::Rc};
+
+// The error is reported as a syntax error at the span of the whole form.
+// A closure can capture state, a table of literals say, from the embedder.
+pub type ReaderMacro = Rc<dyn Fn(&mut ReaderCtx) -> Result<Sexpr, String>>;
+
+#[derive(Clone, Default)]
+pub struct ReaderMacros {
+    macros: HashMap<InternedString, ReaderMacro>,
+}
+
+impl ReaderMacros {
+    pub fn insert(
+        &mut self,
+        name: &str,
+        f: impl Fn(&mut ReaderCtx) -> Result<Sexpr, String> + 'static,
+    ) -> &mut Self {
+        self.macros.insert(InternedString::from(name), Rc::new(f));
+        self
+    }
+
+    pub fn get(&self, name: InternedString) -> Option<ReaderMacro> {
+        self.macros.get(&name).cloned()
+    }
+}
+
+// closures can't be printed, so just their names
+impl fmt::Debug for ReaderMacros {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_set().entries(self.macros.keys()).finish()
+    }
+}
+
+pub struct ReaderCtx {
+    // the name after the #
+    pub name: InternedString,
+    pub datum: Sexpr,
+    // the whole form, from the #
+    pub span: Span,
+}
+
+impl ReaderCtx {
+    // A symbol spanning the whole form, like the reader's own desugarings
+    // use for the heads they add.
+    pub fn sym(&self, name: &str) -> Sexpr {
+        Sexpr::new(
+            SexprKind::Atom(Atom::new(
+                AtomKind::Sym(InternedString::from(name)),
+                self.span,
+            )),
+            self.span,
+        )
+    }
+
+    pub fn list(&self, items: Vec<Sexpr>) -> Sexpr {
+        Sexpr::new(SexprKind::List(List::from(items)), self.span)
+    }
+}
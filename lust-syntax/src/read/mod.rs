@@ -1,99 +1,464 @@
+pub mod corpus;
+pub mod datum;
+pub mod error;
+pub mod escape;
+pub mod interp;
+pub mod label;
+pub mod macros;
+pub mod options;
+pub mod quasi;
 pub mod sexpr;
+pub mod source;
+pub mod stream;
 pub mod token;
+pub mod wisp;
 
 use self::{
+    error::SyntaxError,
+    macros::ReaderCtx,
+    options::{Limits, ReaderOptions},
     sexpr::{Atom, AtomKind, Lit, Root, Sexpr, SexprKind},
-    token::Token,
+    source::TokenSource,
+    token::{LexerState, Token},
+    wisp::Syntax,
 };
 use chumsky::{
     extra,
     input::{Stream, ValueInput},
     prelude::{Input, Rich},
-    primitive::just,
+    primitive::{any, end, just},
+    recovery::{nested_delimiters, skip_then_retry_until, via_parser},
     recursive::recursive,
     select, IterParser, Parser,
 };
 use logos::Logos;
-use lust_utils::{intern::InternedString, list::List, span::Span};
+use lust_utils::{
+    intern::InternedString,
+    list::List,
+    num::Int,
+    span::{FileId, SourceMap, Span},
+};
 use std::vec;
 
-#[derive(Debug, Clone, PartialEq)]
-pub enum SyntaxError<'a> {
-    LexError(Span),
-    ParseError(Rich<'a, Token, Span, &'a str>),
+pub fn read(src: &str) -> (Option<Root>, Vec<SyntaxError>) {
+    read_with(src, &ReaderOptions::default())
 }
 
-pub fn read<'src>(src: &'src str) -> (Option<Root>, Vec<SyntaxError<'src>>) {
-    let mut errs = Vec::new();
-    let mut tokens = vec![];
-    for (res, span) in Token::lexer(src).spanned() {
-        match res {
-            Ok(tok) => tokens.push((tok, Span::from(span))),
-            Err(_) => {
-                errs.push(SyntaxError::LexError(Span::from(span.clone())));
-                tokens.push((Token::Error, Span::from(span)))
+// `read_with`, along with a map of the source for turning the spans of the
+// result and errors into lines and columns.
+pub fn read_with_map(
+    src: &str,
+    opts: &ReaderOptions,
+) -> (Option<Root>, Vec<SyntaxError>, SourceMap) {
+    let (root, errs) = read_with(src, opts);
+    (root, errs, SourceMap::new(src))
+}
+
+pub fn read_with(src: &str, opts: &ReaderOptions) -> (Option<Root>, Vec<SyntaxError>) {
+    if let Some(err) = source_too_long(src.len(), opts) {
+        return (None, vec![err]);
+    }
+    let mut lexer = Token::lexer_with_extras(src, lexer_state(opts));
+    let eoi = Span::from(src.len()..src.len());
+    // #lang line
+    let lang = src.strip_prefix("#lang ").map(|rest| {
+        let end = rest.find('\n').unwrap_or(rest.len());
+        lexer.bump("#lang ".len() + end);
+        rest[..end].trim()
+    });
+    let syntax = lang.and_then(Syntax::from_lang).unwrap_or(opts.syntax);
+    let (root, errs) = match syntax {
+        Syntax::Sexpr => read_tokens_with(lexer, eoi, opts),
+        Syntax::Wisp => {
+            let mut tokens = vec![];
+            while let Some(tok) = lexer.next_token() {
+                tokens.push(tok);
             }
+            read_tokens_with(wisp::lower(src, tokens).into_iter(), eoi, opts)
+        }
+    };
+    let root = root.map(|root| Root {
+        lang: lang.map(InternedString::from),
+        ..root
+    });
+    if opts.file == FileId::default() {
+        return (root, errs);
+    }
+    (
+        root.map(|root| root.in_file(opts.file)),
+        errs.into_iter().map(|err| err.in_file(opts.file)).collect(),
+    )
+}
+
+// The tokens of `src`, comments and bad input included, for tools like
+// highlighters and formatters that work below the level of data. Tokens are
+// as lexed: strings keep their quotes and escapes. Every `Token::Error`,
+// `Token::UnterminatedComment` and `Token::IntOverflow` is also reported as
+// an error.
+pub fn lex(src: &str) -> (Vec<(Token, Span)>, Vec<SyntaxError>) {
+    let mut lexer = Token::lexer(src);
+    let mut tokens = vec![];
+    let mut errs = vec![];
+    while let Some((tok, span)) = lexer.next_token() {
+        match tok {
+            Token::Error => errs.push(SyntaxError::LexError(span)),
+            Token::UnterminatedComment => errs.push(SyntaxError::UnterminatedComment(Span::new(
+                span.start(),
+                span.start() + 2,
+            ))),
+            Token::IntOverflow => errs.push(SyntaxError::IntOverflow(span)),
+            _ => (),
         }
+        tokens.push((tok, span));
     }
+    (tokens, errs)
+}
+
+// Read a string that must hold exactly one datum, like a config value or
+// the argument to read-string.
+pub fn parse_datum(src: &str) -> Result<Sexpr, Vec<SyntaxError>> {
+    let (root, errs) = read(src);
     if !errs.is_empty() {
-        return (None, errs);
+        return Err(errs);
+    }
+    let mut sexprs = root.map(|root| root.sexprs).unwrap_or_default();
+    match sexprs.len() {
+        1 => Ok(sexprs.remove(0)),
+        0 => Err(vec![SyntaxError::ParseError(
+            "expected a datum".to_string(),
+            Span::from(src.len()..src.len()),
+        )]),
+        _ => Err(vec![SyntaxError::ParseError(
+            "expected only one datum".to_string(),
+            sexprs[1].span,
+        )]),
+    }
+}
+
+// Reads source that may not be valid UTF-8. Each invalid sequence is
+// reported and read as spaces, so the rest of the source can still be read
+// and spans still count bytes of the original. A leading byte order mark is
+// skipped.
+pub fn read_bytes(src: &[u8]) -> (Option<Root>, Vec<SyntaxError>) {
+    read_bytes_with(src, &ReaderOptions::default())
+}
+
+pub fn read_bytes_with(src: &[u8], opts: &ReaderOptions) -> (Option<Root>, Vec<SyntaxError>) {
+    if let Some(err) = source_too_long(src.len(), opts) {
+        return (None, vec![err]);
+    }
+    let (bom, rest) = match src.strip_prefix(b"\xef\xbb\xbf") {
+        Some(rest) => (3, rest),
+        None => (0, src),
+    };
+    let (text, errs) = decode(rest);
+    let mut errs = errs
+        .into_iter()
+        .map(|err| err.shift(bom).in_file(opts.file))
+        .collect::<Vec<_>>();
+    let (root, read_errs) = read_with(&text, opts);
+    errs.extend(read_errs.into_iter().map(|err| err.shift(bom)));
+    let root = root.map(|root| Root {
+        sexprs: root.sexprs.iter().map(|sexpr| sexpr.shift(bom)).collect(),
+        span: root.span.shift(bom),
+        ..root
+    });
+    (root, errs)
+}
+
+// `src` as text, with each invalid UTF-8 sequence reported and replaced by
+// a space for each of its bytes, so offsets in the text are offsets in
+// `src`.
+pub fn decode(src: &[u8]) -> (String, Vec<SyntaxError>) {
+    let mut rest = src;
+    let mut text = String::with_capacity(src.len());
+    let mut errs = vec![];
+    while !rest.is_empty() {
+        match std::str::from_utf8(rest) {
+            Ok(s) => {
+                text.push_str(s);
+                break;
+            }
+            Err(err) => {
+                let (valid, invalid) = rest.split_at(err.valid_up_to());
+                text.push_str(std::str::from_utf8(valid).unwrap());
+                let len = err.error_len().unwrap_or(invalid.len());
+                let start = text.len() as u32;
+                errs.push(SyntaxError::InvalidUtf8(Span::new(
+                    start,
+                    start + len as u32,
+                )));
+                text.extend(std::iter::repeat_n(' ', len));
+                rest = &invalid[len..];
+            }
+        }
+    }
+    (text, errs)
+}
+
+// Reads the first datum in `src` and returns it along with the input after
+// it. Errors after the datum aren't reported, so the rest of the input can
+// be incomplete. A datum with errors isn't returned, but the input after it
+// still is. Empty input has no datum and no errors.
+pub fn read_one(src: &str) -> (Option<Sexpr>, &str, Vec<SyntaxError>) {
+    read_one_with(src, &ReaderOptions::default())
+}
+
+pub fn read_one_with<'src>(
+    src: &'src str,
+    opts: &ReaderOptions,
+) -> (Option<Sexpr>, &'src str, Vec<SyntaxError>) {
+    if let Some(err) = source_too_long(src.len(), opts) {
+        return (None, src, vec![err]);
+    }
+    let mut lexer = Token::lexer_with_extras(src, lexer_state(opts));
+    let mut tokens = vec![];
+    while let Some(tok) = lexer.next_token() {
+        tokens.push(tok);
+    }
+    if opts.syntax == Syntax::Wisp {
+        tokens = wisp::lower(src, tokens);
+    }
+    let (tokens, lex_errs) = collect_tokens(tokens.into_iter(), &opts.limits);
+    if tokens.is_empty() && lex_errs.is_empty() {
+        return (None, "", vec![]);
     }
-    println!("tokens: {:?}", tokens);
     let tok_stream = Stream::from_iter(tokens).spanned(Span::from(src.len()..src.len()));
-    let (root, errs) = root_reader().parse(tok_stream).into_output_errors();
+    let (sexpr, errs) = sexpr_reader(opts)
+        .then_ignore(any().repeated())
+        .parse(tok_stream)
+        .into_output_errors();
+    let errs = errs
+        .into_iter()
+        .map(|err| SyntaxError::ParseError(err.to_string(), *err.span()));
+    let (sexpr, rest, errs) = match sexpr {
+        // a malformed list in the datum is recovered, with an error
+        Some(sexpr) => {
+            let end = sexpr.span.end();
+            let mut errs = lex_errs
+                .into_iter()
+                .chain(errs)
+                .filter(|err| err.span().start() < end)
+                .collect::<Vec<_>>();
+            errs.extend(label::check_datum(&sexpr));
+            // the rest is after it either way, so a caller can skip it
+            if errs.is_empty() {
+                (Some(sexpr), &src[end as usize..], errs)
+            } else {
+                (None, &src[end as usize..], errs)
+            }
+        }
+        None => (None, src, lex_errs.into_iter().chain(errs).collect()),
+    };
+    if opts.file == FileId::default() {
+        return (sexpr, rest, errs);
+    }
     (
-        root,
-        errs.into_iter()
-            .map(|err| SyntaxError::ParseError(err))
-            .collect(),
+        sexpr.map(|sexpr| sexpr.in_file(opts.file)),
+        rest,
+        errs.into_iter().map(|err| err.in_file(opts.file)).collect(),
     )
 }
 
+fn source_too_long(len: usize, opts: &ReaderOptions) -> Option<SyntaxError> {
+    let max = opts.limits.max_source_len.filter(|max| len > *max)?;
+    Some(SyntaxError::TooLong(
+        "source",
+        max,
+        Span::from(max..len).in_file(opts.file),
+    ))
+}
+
+fn lexer_state(opts: &ReaderOptions) -> LexerState {
+    LexerState {
+        fold_case: opts.case_insensitive,
+        aliases: opts.aliases.clone(),
+        int_mode: opts.int_mode,
+    }
+}
+
+// Read from any token source, `eoi` is the span reported for errors at the
+// end of input. Lex errors don't stop the read: bad tokens are left out, so
+// the parser still finds the errors after them and the root is as complete
+// as it can be.
+pub fn read_tokens<S: TokenSource>(source: S, eoi: Span) -> (Option<Root>, Vec<SyntaxError>) {
+    read_tokens_with(source, eoi, &ReaderOptions::default())
+}
+
+// `read_tokens` with the options that apply after lexing, its reader
+// macros, limits and extensions.
+pub fn read_tokens_with<S: TokenSource>(
+    source: S,
+    eoi: Span,
+    opts: &ReaderOptions,
+) -> (Option<Root>, Vec<SyntaxError>) {
+    let (tokens, mut errs) = collect_tokens(source, &opts.limits);
+    let tok_stream = Stream::from_iter(tokens).spanned(eoi);
+    let (root, parse_errs) = root_reader(opts).parse(tok_stream).into_output_errors();
+    errs.extend(
+        parse_errs
+            .into_iter()
+            .map(|err| SyntaxError::ParseError(err.to_string(), *err.span())),
+    );
+    if let Some(root) = &root {
+        errs.extend(label::check(root));
+    }
+    (root, errs)
+}
+
+// Decodes strings and drops comments. Tokens that are errors are left out
+// and reported instead.
+fn collect_tokens<S: TokenSource>(
+    mut source: S,
+    limits: &Limits,
+) -> (Vec<(Token, Span)>, Vec<SyntaxError>) {
+    let mut errs = Vec::new();
+    let mut tokens = vec![];
+    while let Some((tok, span)) = source.next_token() {
+        let (what, max) = match tok {
+            Token::String(_) | Token::RawString(_) | Token::InterpString(_) => {
+                ("string", limits.max_string_len)
+            }
+            Token::Ident(_) | Token::Keyword(_) => ("symbol", limits.max_symbol_len),
+            _ => ("", None),
+        };
+        match max {
+            Some(max) if (span.end() - span.start()) as usize > max => {
+                errs.push(SyntaxError::TooLong(what, max, span));
+                continue;
+            }
+            _ => (),
+        }
+        let tok = match tok {
+            // strings are lexed raw, quotes and all, see `TokenSource`
+            Token::String(raw) => match raw.strip_prefix('"').and_then(|s| s.strip_suffix('"')) {
+                Some(body) => match escape::unescape(body, span.start() + 1) {
+                    Ok(s) => Token::String(InternedString::from(s)),
+                    // keep the string, undecoded, so the datum around it
+                    // still reads
+                    Err(err) => {
+                        errs.push(err);
+                        Token::String(InternedString::from(body))
+                    }
+                },
+                None => Token::Error,
+            },
+            Token::RawString(s) => Token::String(s),
+            tok => tok,
+        };
+        match tok {
+            Token::Error => errs.push(SyntaxError::LexError(span)),
+            Token::UnterminatedComment => errs.push(SyntaxError::UnterminatedComment(Span::new(
+                span.start(),
+                span.start() + 2,
+            ))),
+            Token::IntOverflow => errs.push(SyntaxError::IntOverflow(span)),
+            Token::Comment => (),
+            _ => tokens.push((tok, span)),
+        }
+    }
+    (tokens, errs)
+}
+
 fn root_reader<'a, I: ValueInput<'a, Token = Token, Span = Span>>(
+    opts: &'a ReaderOptions,
 ) -> impl Parser<'a, I, Root, extra::Err<Rich<'a, Token, Span>>> {
-    sexpr_reader()
+    let sexpr = sexpr_reader(opts).boxed();
+    // stray tokens between data, like an extra ), are skipped
+    let item = sexpr
+        .clone()
+        .recover_with(skip_then_retry_until(any().ignored(), end()));
+    // a file can be nothing but datum comments
+    just(Token::DatumComment)
+        .ignore_then(sexpr)
         .repeated()
-        .collect()
+        .ignore_then(item.repeated().collect())
         .map_with_span(Root::new)
         .boxed()
 }
 
 fn sexpr_reader<'a, I: ValueInput<'a, Token = Token, Span = Span>>(
+    opts: &'a ReaderOptions,
 ) -> impl Parser<'a, I, Sexpr, extra::Err<Rich<'a, Token, Span>>> {
-    recursive(|sexpr| {
+    let macros = &opts.macros;
+    let ext = opts.extensions;
+    recursive(move |sexpr| {
         // path = symbol ("." symbol)+
         let path = ident_reader()
+            .map_with_span(|name, span: Span| (name, span))
             .then(
                 just(Token::Period)
-                    .ignore_then(ident_reader())
+                    .ignore_then(ident_reader().map_with_span(|name, span: Span| (name, span)))
                     .repeated()
                     .at_least(1)
                     .collect::<Vec<_>>(),
             )
-            .map(|(lhs, rhs)| {
+            .try_map(|(lhs, rhs), span| {
                 let mut v = vec![lhs];
                 v.extend(rhs);
-                v
+                // a.b is a path, but the dot in (a . b) is separated by
+                // whitespace
+                if v.windows(2).any(|w| w[1].1.start() != w[0].1.end() + 1) {
+                    return Err(Rich::custom(span, "expected a path"));
+                }
+                Ok(v.into_iter().map(|(name, _)| name).collect::<Vec<_>>())
             })
-            .map(AtomKind::Path);
+            .map(move |names| {
+                if ext.paths {
+                    AtomKind::Path(names)
+                } else {
+                    let names: Vec<&str> = names.iter().map(|name| &name[..]).collect();
+                    AtomKind::Sym(InternedString::from(names.join(".")))
+                }
+            });
 
         let atom = path
             .or(ident_reader().map(AtomKind::Sym))
+            .or(select! { Token::Keyword(name) => name }.map(move |name| {
+                if ext.keywords {
+                    AtomKind::Keyword(name)
+                } else {
+                    AtomKind::Sym(InternedString::from(format!(":{}", name)))
+                }
+            }))
+            // a lone colon is the type annotation symbol, as in (x : Int)
+            .or(just(Token::Colon).to(AtomKind::Sym(InternedString::from(":"))))
             .or(lit_reader().map(AtomKind::Lit))
+            .or(bytes_reader().map(AtomKind::Lit));
+        // always a signed decimal, so it parses
+        #[cfg(feature = "bignum")]
+        let atom = atom.or(select! { Token::BigInt(digits) => digits }
+            .map(|digits| AtomKind::Lit(Lit::BigInt(digits.parse().unwrap()))));
+        let atom = atom
             .map_with_span(Atom::new)
             .map(SexprKind::Atom)
             .map_with_span(Sexpr::new)
             .boxed();
 
+        // list = "(" (sexpr+ ("." sexpr)?)? ")"
         let list = sexpr
             .clone()
             .repeated()
             .at_least(1)
             .collect::<Vec<_>>()
-            .map(List::from)
-            .map(SexprKind::List)
+            .then(just(Token::Period).ignore_then(sexpr.clone()).or_not())
+            .or_not()
+            .map(|list| match list {
+                Some((items, Some(tail))) => SexprKind::DottedList(List::from(items), tail),
+                Some((items, None)) => SexprKind::List(List::from(items)),
+                None => SexprKind::List(List::Empty),
+            })
+            .delimited_by(just(Token::LParen), just(Token::RParen))
             .map_with_span(Sexpr::new)
-            .delimited_by(just(Token::LParen), just(Token::RParen));
+            .recover_with(via_parser(nested_delimiters(
+                Token::LParen,
+                Token::RParen,
+                [
+                    (Token::LBrack, Token::RBrack),
+                    (Token::LBrace, Token::RBrace),
+                ],
+                malformed,
+            )));
 
         let list_lit = sexpr
             .clone()
@@ -101,101 +466,135 @@ fn sexpr_reader<'a, I: ValueInput<'a, Token = Token, Span = Span>>(
             .at_least(1)
             .collect::<Vec<_>>()
             .map(List::from)
-            .map_with_span(|mut list, span: Span| {
-                list.push_front(Sexpr::new(
-                    SexprKind::Atom(Atom::new(
-                        AtomKind::Sym(InternedString::from("list")),
-                        Span::from(span.start()..span.start()),
-                    )),
-                    span,
-                ));
+            .map_with_span(move |mut list, span: Span| {
+                if ext.list_literals {
+                    list.push_front(Sexpr::new(
+                        SexprKind::Atom(Atom::new(
+                            AtomKind::Sym(InternedString::from("list")),
+                            Span::from(span.start()..span.start()),
+                        )),
+                        span,
+                    ));
+                }
                 SexprKind::List(list)
             })
+            .delimited_by(just(Token::LBrack), just(Token::RBrack))
             .map_with_span(Sexpr::new)
-            .delimited_by(just(Token::LBrack), just(Token::RBrack));
+            .recover_with(via_parser(nested_delimiters(
+                Token::LBrack,
+                Token::RBrack,
+                [
+                    (Token::LParen, Token::RParen),
+                    (Token::LBrace, Token::RBrace),
+                ],
+                malformed,
+            )));
 
-        let vector = sexpr
+        // {k1 v1 k2 v2} = (map k1 v1 k2 v2)
+        let map_lit = sexpr
             .clone()
             .repeated()
             .collect::<Vec<_>>()
-            .map(List::from)
-            .map(SexprKind::List)
-            .map_with_span(Sexpr::new)
-            .delimited_by(just(Token::HashLBrack), just(Token::RBrack));
-
-        // quote = "'" sexpr
-        let quote = just(Token::Quote)
-            .map_with_span(|_, span| span)
-            .then(sexpr.clone())
-            .map(|(span, sexpr)| {
-                let mut list = List::Empty;
-                list.push_front(sexpr);
-                list.push_front(Sexpr::new(
-                    SexprKind::Atom(Atom::new(
-                        AtomKind::Sym(InternedString::from("quote")),
+            .delimited_by(just(Token::LBrace), just(Token::RBrace))
+            .validate(|items, span: Span, emitter| {
+                if items.len() % 2 != 0 {
+                    emitter.emit(Rich::custom(
                         span,
-                    )),
-                    span,
-                ));
-                SexprKind::List(list)
-            })
-            .map_with_span(Sexpr::new);
-
-        let quasiquote = just(Token::Backquote)
-            .map_with_span(|_, span| span)
-            .then(sexpr.clone())
-            .map(|(span, sexpr)| {
-                let mut list = List::Empty;
-                list.push_front(sexpr);
+                        "expected a value for every key in a map",
+                    ));
+                }
+                let mut list = List::from(items);
                 list.push_front(Sexpr::new(
                     SexprKind::Atom(Atom::new(
-                        AtomKind::Sym(InternedString::from("quasiquote")),
-                        span,
+                        AtomKind::Sym(InternedString::from("map")),
+                        Span::from(span.start()..span.start()),
                     )),
                     span,
                 ));
                 SexprKind::List(list)
             })
-            .map_with_span(Sexpr::new);
+            .map_with_span(Sexpr::new)
+            .validate(move |sexpr, span, emitter| {
+                if !ext.maps {
+                    emitter.emit(Rich::custom(span, "map literals are turned off"));
+                }
+                sexpr
+            });
 
-        let unquote = just(Token::Comma)
-            .map_with_span(|_, span| span)
-            .then(sexpr.clone())
-            .map(|(span, sexpr)| {
-                let mut list = List::Empty;
-                list.push_front(sexpr);
+        // #{a b c} = (set a b c)
+        let set_lit = sexpr
+            .clone()
+            .repeated()
+            .collect::<Vec<_>>()
+            .map(List::from)
+            .map_with_span(|mut list, span: Span| {
                 list.push_front(Sexpr::new(
                     SexprKind::Atom(Atom::new(
-                        AtomKind::Sym(InternedString::from("unquote")),
-                        span,
+                        AtomKind::Sym(InternedString::from("set")),
+                        Span::from(span.start()..span.start()),
                     )),
                     span,
                 ));
                 SexprKind::List(list)
             })
-            .map_with_span(Sexpr::new);
+            .delimited_by(just(Token::HashLBrace), just(Token::RBrace))
+            .map_with_span(Sexpr::new)
+            .validate(move |sexpr, span, emitter| {
+                if !ext.sets {
+                    emitter.emit(Rich::custom(span, "set literals are turned off"));
+                }
+                sexpr
+            });
 
-        let unquote_splice = just(Token::CommaAt)
-            .map_with_span(|_, span| span)
-            .then(sexpr.clone())
-            .map(|(span, sexpr)| {
-                let mut list = List::Empty;
-                list.push_front(sexpr);
-                list.push_front(Sexpr::new(
-                    SexprKind::Atom(Atom::new(
-                        AtomKind::Sym(InternedString::from("unquote-splicing")),
-                        span,
-                    )),
-                    span,
-                ));
-                SexprKind::List(list)
-            })
-            .map_with_span(Sexpr::new);
+        let vector = sexpr
+            .clone()
+            .repeated()
+            .collect::<Vec<_>>()
+            .map(List::from)
+            .map(SexprKind::List)
+            .delimited_by(just(Token::HashLBrack), just(Token::RBrack))
+            .map_with_span(Sexpr::new)
+            .validate(move |sexpr, span, emitter| {
+                if !ext.vectors {
+                    emitter.emit(Rich::custom(span, "vectors are turned off"));
+                }
+                sexpr
+            });
+
+        // quote = "'" sexpr, and likewise for the others. The head symbol
+        // is at the quote and the list spans from it to the end of the
+        // datum, not over any #; comments after it.
+        let quoted = |tok: Token, name: &'static str| {
+            just(tok)
+                .map_with_span(|_, span: Span| span)
+                .then(sexpr.clone())
+                .map(move |(quote, sexpr): (Span, Sexpr)| {
+                    let span = quote.extend(sexpr.span);
+                    let mut list = List::Empty;
+                    list.push_front(sexpr);
+                    list.push_front(Sexpr::new(
+                        SexprKind::Atom(Atom::new(
+                            AtomKind::Sym(InternedString::from(name)),
+                            quote,
+                        )),
+                        quote,
+                    ));
+                    Sexpr::new(SexprKind::List(list), span)
+                })
+        };
+        let quote = quoted(Token::Quote, "quote");
+        let quasiquote = quoted(Token::Backquote, "quasiquote");
+        let unquote = quoted(Token::Comma, "unquote");
+        let unquote_splice = quoted(Token::CommaAt, "unquote-splicing");
 
         // map foo... to (vargs foo)
         let variadic = ident_reader()
             .then_ignore(just(Token::Ellipsis))
-            .map_with_span(|name, span| {
+            .map_with_span(move |name, span| {
+                if !ext.variadic {
+                    let name = InternedString::from(format!("{}...", name));
+                    return SexprKind::Atom(Atom::new(AtomKind::Sym(name), span));
+                }
                 let mut list = List::Empty;
                 list.push_front(Sexpr::new(
                     SexprKind::Atom(Atom::new(AtomKind::Sym(name), span)),
@@ -210,32 +609,927 @@ fn sexpr_reader<'a, I: ValueInput<'a, Token = Token, Span = Span>>(
             .map_with_span(Sexpr::new)
             .boxed();
 
-        variadic
+        // #0=datum and #0#
+        let datum_label = select! { Token::DatumLabel(n) => n }
+            .then(sexpr.clone())
+            .map(|(n, sexpr)| SexprKind::DatumLabel(n, sexpr))
+            .map_with_span(Sexpr::new);
+
+        let datum_ref = select! { Token::DatumRef(n) => n }
+            .map(SexprKind::DatumRef)
+            .map_with_span(Sexpr::new);
+
+        // an error in the string is reported where it is, and the string
+        // read as malformed, like a failed reader macro
+        let interp = select! { Token::InterpString(interp) => interp }.validate(
+            move |interp, span, emitter| {
+                if !ext.interpolation {
+                    emitter.emit(Rich::custom(span, "interpolated strings are turned off"));
+                }
+                interp::desugar(&interp, span, opts).unwrap_or_else(|err| {
+                    emitter.emit(Rich::custom(err.span(), err));
+                    malformed(span)
+                })
+            },
+        );
+
+        // #; datum, the datum is still read so it has to be well formed
+        let skip = just(Token::DatumComment)
+            .ignore_then(sexpr.clone())
+            .repeated()
+            .boxed();
+
+        // #name datum, handed to the embedder's reader macro for name. A
+        // macro that fails is reported and the form is read as malformed.
+        let dispatch = just(Token::Hash)
+            .map_with_span(|_, span: Span| span)
+            .then(ident_reader().map_with_span(|name, span: Span| (name, span)))
+            .then(sexpr.clone())
+            .validate(move |((hash, (name, name_span)), datum), span, emitter| {
+                let res = if name_span.start() != hash.end() {
+                    Err("expected a reader macro name after #".to_string())
+                } else if let Some(f) = macros.get(name) {
+                    f(&mut ReaderCtx { name, datum, span })
+                } else {
+                    Err(format!("unknown reader macro #{}", name))
+                };
+                res.unwrap_or_else(|msg| {
+                    emitter.emit(Rich::custom(span, msg));
+                    malformed(span)
+                })
+            });
+
+        let sexpr = dispatch
+            .or(variadic)
+            .or(datum_label)
+            .or(datum_ref)
+            .or(interp)
             .or(list)
             .or(list_lit)
+            .or(map_lit)
+            .or(set_lit)
             .or(vector)
             .or(quote)
             .or(quasiquote)
             .or(unquote)
             .or(unquote_splice)
-            .or(atom)
+            .or(atom);
+
+        // comments before a datum and after the last one in a list
+        skip.clone().ignore_then(sexpr).then_ignore(skip)
     })
 }
 
+// What a list that couldn't be read is replaced with, so the reader can
+// carry on after it. The error is reported either way.
+fn malformed(span: Span) -> Sexpr {
+    Sexpr::new(SexprKind::List(List::Empty), span)
+}
+
 fn ident_reader<'a, I: ValueInput<'a, Token = Token, Span = Span>>(
-) -> impl Parser<'a, I, InternedString, extra::Err<Rich<'a, Token, Span>>> {
+) -> impl Parser<'a, I, InternedString, extra::Err<Rich<'a, Token, Span>>> + Clone {
     select! {
         Token::Ident(name) => name,
     }
 }
 
 fn lit_reader<'a, I: ValueInput<'a, Token = Token, Span = Span>>(
-) -> impl Parser<'a, I, Lit, extra::Err<Rich<'a, Token, Span>>> {
+) -> impl Parser<'a, I, Lit, extra::Err<Rich<'a, Token, Span>>> + Clone {
     select! {
         Token::Int(n) => Lit::Int(n),
         Token::Real(n) => Lit::Real(n),
         Token::Rational(n) => Lit::Rational(n),
+        Token::Complex(n) => Lit::Complex(n),
         Token::Bool(b) => Lit::Bool(b),
         Token::String(s) => Lit::String(s),
+        Token::Char(c) => Lit::Char(c),
+    }
+}
+
+// #u8(0 255 16)
+fn bytes_reader<'a, I: ValueInput<'a, Token = Token, Span = Span>>(
+) -> impl Parser<'a, I, Lit, extra::Err<Rich<'a, Token, Span>>> {
+    // an out of range byte is reported without stopping the list
+    select! { Token::Int(n) => n }
+        .validate(|n: Int, span, emitter| {
+            u8::try_from(n.value()).unwrap_or_else(|_| {
+                emitter.emit(Rich::custom(span, format!("byte out of range: {}", n)));
+                0
+            })
+        })
+        .repeated()
+        .collect()
+        .delimited_by(just(Token::HashU8LParen), just(Token::RParen))
+        .map(Lit::Bytes)
+        .recover_with(via_parser(nested_delimiters(
+            Token::HashU8LParen,
+            Token::RParen,
+            [],
+            |_| Lit::Bytes(vec![]),
+        )))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        decode,
+        error::SyntaxError,
+        lex,
+        options::{Extensions, IntMode, Limits, ReaderOptions},
+        parse_datum, read_bytes, read_one, read_one_with, read_tokens, read_with,
+        sexpr::{Atom, AtomKind, Lit, Sexpr, SexprKind},
+        token::Token,
+        wisp::Syntax,
+    };
+    use lust_utils::{
+        intern::InternedString,
+        list::List,
+        span::{SourceCache, Span},
+    };
+    use std::{cell::RefCell, rc::Rc};
+
+    fn read_str(src: &str, opts: &ReaderOptions) -> String {
+        match read_with(src, opts) {
+            (Some(root), errs) if errs.is_empty() => root.to_string(),
+            (_, errs) => panic!("failed to read {:?}: {:?}", src, errs),
+        }
+    }
+
+    #[test]
+    fn case_insensitive() {
+        let opts = ReaderOptions {
+            case_insensitive: true,
+            ..Default::default()
+        };
+        assert_eq!(read_str("(Foo BAR \"Baz\")", &opts), "(foo bar \"Baz\")\n");
+        assert_eq!(read_str("(Foo #!no-fold-case Bar)", &opts), "(foo Bar)\n");
+    }
+
+    #[test]
+    fn aliases() {
+        let mut opts = ReaderOptions::default();
+        assert_eq!(read_str("(λ (x) x) →", &opts), "(fn (x) x)\n->\n");
+        opts.aliases.insert("≤".into(), "<=".into());
+        assert_eq!(read_str("(≤ 1 2)", &opts), "(<= 1 2)\n");
+        opts.aliases.clear();
+        assert_eq!(read_str("λ", &opts), "λ\n");
+    }
+
+    #[test]
+    fn token_source() {
+        let ident = |name: &str, start: u32| {
+            (
+                Token::Ident(name.into()),
+                Span::new(start, start + name.len() as u32),
+            )
+        };
+        let tokens = vec![
+            (Token::LParen, Span::new(0, 1)),
+            ident("f", 1),
+            ident("x", 3),
+            (Token::RParen, Span::new(4, 5)),
+        ];
+        match read_tokens(tokens.into_iter(), Span::new(5, 5)) {
+            (Some(root), errs) if errs.is_empty() => {
+                assert_eq!(root.to_string(), "(f x)\n");
+                assert_eq!(root.sexprs[0].span, Span::new(0, 5));
+            }
+            (_, errs) => panic!("failed to read tokens: {:?}", errs),
+        }
+        // strings come quoted, as the lexer makes them
+        let tokens = vec![
+            (Token::String("\"a\\nb\"".into()), Span::new(0, 6)),
+            (Token::String("c".into()), Span::new(7, 8)),
+            (Token::String("\"".into()), Span::new(9, 10)),
+        ];
+        let (root, errs) = read_tokens(tokens.into_iter(), Span::new(10, 10));
+        assert_eq!(root.unwrap().to_string(), "\"a\\nb\"\n");
+        assert_eq!(
+            errs,
+            vec![
+                SyntaxError::LexError(Span::new(7, 8)),
+                SyntaxError::LexError(Span::new(9, 10)),
+            ]
+        );
+    }
+
+    #[test]
+    fn wisp() {
+        let opts = ReaderOptions {
+            syntax: Syntax::Wisp,
+            ..Default::default()
+        };
+        let src = "def (fib n)\n  if (<= n 1) n\n    + (fib (- n 1))\n      fib (- n 2)\n\ndisplay\n  . \"done\"\n";
+        assert_eq!(
+            read_str(src, &opts),
+            "(def (fib n) (if (<= n 1) n (+ (fib (- n 1)) (fib (- n 2)))))\n(display \"done\")\n"
+        );
+        assert_eq!(
+            read_str("def m {:a 1\n  :b 2}\n", &opts),
+            "(def m (map :a 1 :b 2))\n"
+        );
+    }
+
+    #[test]
+    fn lang() {
+        let opts = ReaderOptions::default();
+        match read_with("#lang lust/base\n(def x 1)\n", &opts) {
+            (Some(root), errs) if errs.is_empty() => {
+                assert_eq!(root.lang, Some("lust/base".into()));
+                assert_eq!(root.sexprs[0].span, Span::new(16, 25));
+                assert_eq!(root.to_string(), "#lang lust/base\n(def x 1)\n");
+            }
+            (_, errs) => panic!("failed to read: {:?}", errs),
+        }
+        assert_eq!(
+            read_str("#lang wisp\ndisplay x\n", &opts),
+            "#lang wisp\n(display x)\n"
+        );
+    }
+
+    #[test]
+    fn fold_case_directive() {
+        let opts = ReaderOptions::default();
+        assert_eq!(
+            read_str("Foo #!fold-case Bar #!no-fold-case Baz", &opts),
+            "Foo\nbar\nBaz\n"
+        );
+    }
+
+    #[test]
+    fn normalized_symbols() {
+        let opts = ReaderOptions::default();
+        let (root, errs) = read_with("caf\u{e9} cafe\u{301} :cafe\u{301} |cafe\u{301}|", &opts);
+        assert!(errs.is_empty(), "{:?}", errs);
+        let kinds: Vec<_> = root
+            .unwrap()
+            .sexprs
+            .iter()
+            .map(|sexpr| match sexpr.kind.as_ref() {
+                SexprKind::Atom(atom) => atom.kind.as_ref().clone(),
+                kind => panic!("expected an atom, got {:?}", kind),
+            })
+            .collect();
+        assert_eq!(
+            kinds,
+            vec![
+                AtomKind::Sym("caf\u{e9}".into()),
+                AtomKind::Sym("caf\u{e9}".into()),
+                AtomKind::Keyword("caf\u{e9}".into()),
+                AtomKind::Sym("caf\u{e9}".into()),
+            ]
+        );
+    }
+
+    #[test]
+    fn dotted_list() {
+        let opts = ReaderOptions::default();
+        assert_eq!(
+            read_str("(a . b) (a b . (c d)) (f x . rest) a.b.c", &opts),
+            "(a . b)\n(a b . (c d))\n(f x . rest)\na.b.c\n"
+        );
+        let (root, _) = read_with("(a . b) a.b", &opts);
+        let root = root.unwrap();
+        let SexprKind::DottedList(list, tail) = root.sexprs[0].kind.as_ref() else {
+            panic!("expected a dotted list");
+        };
+        assert_eq!((list.iter().count(), tail.span), (1, Span::from(5..6u32)));
+        let SexprKind::Atom(atom) = root.sexprs[1].kind.as_ref() else {
+            panic!("expected an atom");
+        };
+        assert_eq!(
+            atom.kind.as_ref(),
+            &AtomKind::Path(vec!["a".into(), "b".into()])
+        );
+        assert!(!read_with("(. a)", &opts).1.is_empty());
+        assert!(!read_with("(a . b c)", &opts).1.is_empty());
+    }
+
+    #[test]
+    fn comments() {
+        let opts = ReaderOptions::default();
+        assert_eq!(
+            read_str("(a ; one\n #| two #| three |# |# b) #||#", &opts),
+            "(a b)\n"
+        );
+        assert_eq!(
+            read_with("(a) #| one #| two |#", &opts).1,
+            vec![SyntaxError::UnterminatedComment(Span::new(4, 6))]
+        );
+    }
+
+    #[test]
+    fn lex_tokens() {
+        let (tokens, errs) = lex("(f \"a\\n\") ; hi\n#!/x");
+        let kinds = tokens
+            .iter()
+            .map(|(tok, _)| tok.to_string())
+            .collect::<Vec<_>>();
+        assert_eq!(
+            kinds,
+            vec!["(", "Ident(f)", "String(\"a\\n\")", ")", "Comment", "Error"]
+        );
+        assert_eq!(errs, vec![SyntaxError::LexError(Span::new(15, 19))]);
+        assert!(tokens[4].0.is_trivia());
+        assert_eq!(tokens[0].0.text(), Some("("));
+        assert_eq!(tokens[1].0.text(), None);
+    }
+
+    #[test]
+    fn string_source() {
+        let src = r#"("a\x41;\n" #r"b\q")"#;
+        let root = read_with(src, &ReaderOptions::default()).0.unwrap();
+        let SexprKind::List(list) = root.sexprs[0].kind.as_ref() else {
+            panic!("expected a list");
+        };
+        let atoms = list
+            .iter()
+            .map(|sexpr| match sexpr.kind.as_ref() {
+                SexprKind::Atom(atom) => atom,
+                kind => panic!("expected an atom, got {}", kind),
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(atoms[0].to_string(), r#""aA\n""#);
+        assert_eq!(atoms[0].source(src), r#""a\x41;\n""#);
+        assert_eq!(atoms[1].source(src), r#"#r"b\q""#);
+    }
+
+    #[test]
+    fn shebang() {
+        let opts = ReaderOptions::default();
+        assert_eq!(
+            read_str("#!/usr/bin/env lust\n(display 1)\n", &opts),
+            "(display 1)\n"
+        );
+        assert_eq!(
+            read_with("(a)\n#!/usr/bin/env lust", &opts).1,
+            vec![SyntaxError::LexError(Span::new(4, 23))]
+        );
+    }
+
+    #[test]
+    fn datum_comments() {
+        let opts = ReaderOptions::default();
+        assert_eq!(
+            read_str("(a #;(b c) d #;e) #;#;f g h #;i", &opts),
+            "(a d)\nh\n"
+        );
+        assert_eq!(read_str("#;(a)", &opts), "");
+        let (root, _) = read_with("#;a (b)", &opts);
+        assert_eq!(root.unwrap().sexprs[0].span, Span::new(4, 7));
+        assert!(!read_with("(a #;(b)", &opts).1.is_empty());
+        assert!(!read_with("(a #;)", &opts).1.is_empty());
+    }
+
+    #[test]
+    fn string_escapes() {
+        let opts = ReaderOptions::default();
+        assert_eq!(
+            read_str(r#"("a\tb\x41;" "\u{3bb}\"")"#, &opts),
+            "(\"a\\tbA\" \"λ\\\"\")\n"
+        );
+        assert_eq!(
+            read_with(r#"(f "ab\q")"#, &opts).1,
+            vec![SyntaxError::InvalidEscape(Span::new(6, 8))]
+        );
+    }
+
+    #[test]
+    fn raw_strings() {
+        let opts = ReaderOptions::default();
+        assert_eq!(
+            read_str(r##"(#r"C:\dir\n" #r#"say "hi""#)"##, &opts),
+            "(\"C:\\\\dir\\\\n\" \"say \\\"hi\\\"\")\n"
+        );
+        assert_eq!(read_str("#r\"a\nb\"", &opts), "\"a\\nb\"\n");
+        assert_eq!(
+            read_with("#r#\"a\" b", &opts).1,
+            vec![SyntaxError::LexError(Span::new(0, 8))]
+        );
+    }
+
+    #[test]
+    fn pipe_symbols() {
+        let opts = ReaderOptions {
+            case_insensitive: true,
+            ..Default::default()
+        };
+        assert_eq!(
+            read_str(r"(|hello world| |a\|b| |42| |Abc| Abc)", &opts),
+            "(|hello world| |a\\|b| |42| Abc abc)\n"
+        );
+    }
+
+    #[test]
+    fn keywords() {
+        let opts = ReaderOptions {
+            case_insensitive: true,
+            ..Default::default()
+        };
+        let (root, errs) = read_with("(:Foo x : Int)", &opts);
+        assert!(errs.is_empty(), "{:?}", errs);
+        let root = root.unwrap();
+        assert_eq!(root.to_string(), "(:foo x : int)\n");
+        let SexprKind::List(list) = root.sexprs[0].kind.as_ref() else {
+            panic!("expected a list");
+        };
+        let kinds = list
+            .iter()
+            .map(|sexpr| match sexpr.kind.as_ref() {
+                SexprKind::Atom(atom) => atom.kind.as_ref().clone(),
+                _ => panic!("expected an atom"),
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(
+            kinds,
+            [
+                AtomKind::Keyword("foo".into()),
+                AtomKind::Sym("x".into()),
+                AtomKind::Sym(":".into()),
+                AtomKind::Sym("int".into()),
+            ]
+        );
+        let (root, errs) = read_with("#:when", &ReaderOptions::default());
+        assert!(errs.is_empty(), "{:?}", errs);
+        assert_eq!(root.unwrap().to_string(), ":when\n");
+    }
+
+    #[test]
+    fn empty_list() {
+        let opts = ReaderOptions::default();
+        assert_eq!(
+            read_str("() (fn () x) (a . ())", &opts),
+            "()\n(fn () x)\n(a . ())\n"
+        );
+        let (root, _) = read_with("( )", &opts);
+        let root = root.unwrap();
+        assert_eq!(root.sexprs[0].kind.as_ref(), &SexprKind::List(List::Empty));
+        assert_eq!(root.sexprs[0].span, Span::new(0, 3));
+    }
+
+    #[test]
+    fn map_literals() {
+        let opts = ReaderOptions::default();
+        assert_eq!(
+            read_str("{:a 1 :b {}} {}", &opts),
+            "(map :a 1 :b (map))\n(map)\n"
+        );
+        match &read_with("(f {:a 1 :b})", &opts).1[..] {
+            [SyntaxError::ParseError(_, span)] => assert_eq!(*span, Span::new(3, 12)),
+            errs => panic!("expected one error, got {:?}", errs),
+        }
+    }
+
+    #[test]
+    fn set_literals() {
+        let opts = ReaderOptions::default();
+        assert_eq!(
+            read_str("#{1 2 #{}} #{:a {:b 1}}", &opts),
+            "(set 1 2 (set))\n(set :a (map :b 1))\n"
+        );
+        assert!(!read_with("#{1 2", &opts).1.is_empty());
+    }
+
+    #[test]
+    fn bytevectors() {
+        let opts = ReaderOptions::default();
+        assert_eq!(
+            read_str("#u8(0 255 16) #u8()", &opts),
+            "#u8(0 255 16)\n#u8()\n"
+        );
+        match &read_with("#u8(1 256 2)", &opts).1[..] {
+            [SyntaxError::ParseError(msg, span)] => {
+                assert_eq!(msg, "byte out of range: 256");
+                assert_eq!(*span, Span::new(6, 9));
+            }
+            errs => panic!("expected one error, got {:?}", errs),
+        }
+    }
+
+    #[test]
+    fn reader_macros() {
+        let mut opts = ReaderOptions::default();
+        opts.macros
+            .insert("re", |ctx| {
+                Ok(ctx.list(vec![ctx.sym("regex"), ctx.datum.clone()]))
+            })
+            .insert("date", |ctx| match ctx.datum.kind.as_ref() {
+                SexprKind::Atom(atom) if matches!(*atom.kind, AtomKind::Lit(Lit::String(_))) => {
+                    Ok(ctx.list(vec![ctx.sym("date"), ctx.datum.clone()]))
+                }
+                _ => Err("expected a date string".to_string()),
+            });
+        assert_eq!(
+            read_str(r#"(#re"a+" #date "2024-01-01")"#, &opts),
+            "((regex \"a+\") (date \"2024-01-01\"))\n"
+        );
+        match &read_with("(#date 1)", &opts).1[..] {
+            [SyntaxError::ParseError(msg, span)] => {
+                assert_eq!(msg, "expected a date string");
+                assert_eq!(*span, Span::new(1, 8));
+            }
+            errs => panic!("expected one error, got {:?}", errs),
+        }
+        match &read_with("#nope 1", &opts).1[..] {
+            [SyntaxError::ParseError(msg, _)] => assert_eq!(msg, "unknown reader macro #nope"),
+            errs => panic!("expected one error, got {:?}", errs),
+        }
+        // a macro can close over the embedder's state
+        let units = Rc::new(RefCell::new(vec![]));
+        let seen = units.clone();
+        opts.macros.insert("unit", move |ctx| {
+            seen.borrow_mut().push(ctx.datum.to_string());
+            Ok(ctx.datum.clone())
+        });
+        assert_eq!(read_str("(#unit kg #unit m)", &opts), "(kg m)\n");
+        assert_eq!(*units.borrow(), vec!["kg", "m"]);
+    }
+
+    #[test]
+    fn extensions() {
+        let strict = ReaderOptions {
+            extensions: Extensions::none(),
+            ..Default::default()
+        };
+        assert_eq!(
+            read_str("(f [a b] xs... :k)", &ReaderOptions::default()),
+            "(f (list a b) (varg xs) :k)\n"
+        );
+        // xs... is a plain symbol here, so it's printed so it reads back
+        // as one with the default options
+        assert_eq!(
+            read_str("(f [a b] xs... :k)", &strict),
+            "(f (a b) |xs...| |:k|)\n"
+        );
+        let root = read_with("a.b :k", &strict).0.unwrap();
+        let kinds: Vec<_> = root
+            .sexprs
+            .iter()
+            .map(|sexpr| match sexpr.kind.as_ref() {
+                SexprKind::Atom(atom) => atom.kind.as_ref().clone(),
+                kind => panic!("expected an atom, got {:?}", kind),
+            })
+            .collect();
+        assert_eq!(
+            kinds,
+            vec![AtomKind::Sym("a.b".into()), AtomKind::Sym(":k".into())]
+        );
+        match &read_with("(f #[1 2])", &strict).1[..] {
+            [SyntaxError::ParseError(msg, span)] => {
+                assert_eq!(msg, "vectors are turned off");
+                assert_eq!(*span, Span::new(3, 9));
+            }
+            errs => panic!("expected one error, got {:?}", errs),
+        }
+    }
+
+    #[test]
+    fn reals_print_as_reals() {
+        let opts = ReaderOptions::default();
+        let printed = read_str("(1.0 -0.0 0.1 1e300 2.5e-10)", &opts);
+        assert_eq!(printed, "(1.0 -0.0 0.1 1e300 2.5e-10)\n");
+        assert_eq!(read_str(&printed, &opts), printed);
+    }
+
+    #[test]
+    fn complex() {
+        let opts = ReaderOptions::default();
+        let printed = read_str("(3+4i -2.5i +i 2@0 1e2-inf.0i)", &opts);
+        assert_eq!(printed, "(3.0+4.0i -2.5i +1.0i 2.0+0.0i 100.0-inf.0i)\n");
+        assert_eq!(read_str(&printed, &opts), printed);
+        // i on its own is still a symbol, and so is - followed by a name
+        assert_eq!(read_str("(i -in)", &opts), "(i -in)\n");
+    }
+
+    #[test]
+    fn int_modes() {
+        let read_mode = |int_mode| {
+            let opts = ReaderOptions {
+                int_mode,
+                ..Default::default()
+            };
+            read_with("(1 9223372036854775808)", &opts)
+        };
+        #[cfg(feature = "bignum")]
+        assert_eq!(
+            read_mode(IntMode::Big).0.unwrap().to_string(),
+            "(1 9223372036854775808)\n"
+        );
+        assert_eq!(
+            read_mode(IntMode::Checked).1,
+            vec![SyntaxError::IntOverflow(Span::new(3, 22))]
+        );
+        assert_eq!(
+            read_mode(IntMode::Wrapping).0.unwrap().to_string(),
+            "(1 -9223372036854775808)\n"
+        );
+        #[cfg(feature = "bignum")]
+        assert_eq!(
+            read_with("-9223372036854775809", &ReaderOptions::default())
+                .0
+                .unwrap()
+                .to_string(),
+            "-9223372036854775809\n"
+        );
+    }
+
+    #[test]
+    fn radix_int_modes() {
+        let read_mode = |int_mode| {
+            let opts = ReaderOptions {
+                int_mode,
+                ..Default::default()
+            };
+            read_with("(#xffffffffffffffff 0xffffffffffffffff)", &opts)
+        };
+        #[cfg(feature = "bignum")]
+        assert_eq!(
+            read_mode(IntMode::Big).0.unwrap().to_string(),
+            "(18446744073709551615 18446744073709551615)\n"
+        );
+        assert_eq!(
+            read_mode(IntMode::Checked).1,
+            vec![
+                SyntaxError::IntOverflow(Span::new(1, 19)),
+                SyntaxError::IntOverflow(Span::new(20, 38)),
+            ]
+        );
+        assert_eq!(
+            read_mode(IntMode::Wrapping).0.unwrap().to_string(),
+            "(-1 -1)\n"
+        );
+    }
+
+    #[test]
+    fn files() {
+        let mut cache = SourceCache::new();
+        let src = "(f x)\n(g #0#)";
+        let opts = ReaderOptions {
+            file: cache.add("main.lust", src),
+            ..Default::default()
+        };
+        let (root, errs) = read_with(src, &opts);
+        let root = root.unwrap();
+        assert_eq!(root.sexprs[0].span.file(), opts.file);
+        assert_eq!(
+            cache.location(root.sexprs[1].span).unwrap(),
+            "main.lust:2:1"
+        );
+        assert_eq!(cache.location(errs[0].span()).unwrap(), "main.lust:2:4");
+        assert_eq!(read_one_with(src, &opts).0.unwrap().span.file(), opts.file);
+        // spans are still compared by offset within the same file
+        assert_ne!(root.sexprs[0].span, Span::new(0, 5));
+        assert_eq!(root.sexprs[0].span, Span::new(0, 5).in_file(opts.file));
+    }
+
+    #[test]
+    fn quote_spans() {
+        let (root, errs) = read_with("('(a b) #;c `,@d)", &ReaderOptions::default());
+        assert!(errs.is_empty(), "{:?}", errs);
+        let items = |sexpr: &Sexpr| match sexpr.kind.as_ref() {
+            SexprKind::List(list) => list.iter().cloned().collect::<Vec<_>>(),
+            kind => panic!("expected a list, got {:?}", kind),
+        };
+        let spans = |sexpr: &Sexpr| {
+            items(sexpr)
+                .iter()
+                .map(|item| item.span)
+                .collect::<Vec<_>>()
+        };
+        let list = items(&root.unwrap().sexprs[0]);
+        // the wrapper covers the quote and the datum, the datum keeps its own
+        assert_eq!(list[0].span, Span::new(1, 7));
+        assert_eq!(spans(&list[0]), vec![Span::new(1, 2), Span::new(2, 7)]);
+        assert_eq!(list[1].span, Span::new(12, 16));
+        assert_eq!(spans(&list[1]), vec![Span::new(12, 13), Span::new(13, 16)]);
+        assert_eq!(
+            spans(&items(&list[1])[1]),
+            vec![Span::new(13, 15), Span::new(15, 16)]
+        );
+    }
+
+    #[test]
+    fn read_one_datum() {
+        let (sexpr, rest, errs) = read_one("(a b) c");
+        assert_eq!(sexpr.unwrap().to_string(), "(a b)");
+        assert_eq!((rest, errs), (" c", vec![]));
+        let (sexpr, rest, _) = read_one("#;x 'y ; z");
+        assert_eq!(sexpr.unwrap().to_string(), "(quote y)");
+        assert_eq!(rest, " ; z");
+        // the rest isn't read
+        let (sexpr, rest, errs) = read_one("a \"b");
+        assert_eq!(sexpr.unwrap().to_string(), "a");
+        assert_eq!((rest, errs), (" \"b", vec![]));
+        let (sexpr, rest, errs) = read_one("(a");
+        assert!(sexpr.is_none() && !errs.is_empty());
+        assert_eq!(rest, "(a");
+        assert_eq!(read_one(" ; nothing"), (None, "", vec![]));
+    }
+
+    #[test]
+    fn invalid_utf8() {
+        let (root, errs) = read_bytes(b"\xef\xbb\xbf(a \"b\xffc\" \xe2\x82)");
+        assert_eq!(root.unwrap().to_string(), "(a \"b c\")\n");
+        assert_eq!(
+            errs,
+            vec![
+                SyntaxError::InvalidUtf8(Span::new(8, 9)),
+                SyntaxError::InvalidUtf8(Span::new(12, 14)),
+            ]
+        );
+        let (root, errs) = read_bytes(b"\xef\xbb\xbf(a)");
+        assert!(errs.is_empty());
+        assert_eq!(root.unwrap().sexprs[0].span, Span::new(3, 6));
+        assert_eq!(
+            decode(b"a\xffb\xe2\x82"),
+            (
+                "a b  ".to_string(),
+                vec![
+                    SyntaxError::InvalidUtf8(Span::new(1, 2)),
+                    SyntaxError::InvalidUtf8(Span::new(3, 5)),
+                ]
+            )
+        );
+    }
+
+    #[test]
+    fn limits() {
+        let opts = ReaderOptions {
+            limits: Limits {
+                max_source_len: Some(24),
+                max_string_len: Some(5),
+                max_symbol_len: Some(3),
+            },
+            ..Default::default()
+        };
+        assert_eq!(read_str("(abc \"abc\")", &opts), "(abc \"abc\")\n");
+        assert_eq!(
+            read_with("(abcd \"abcd\" :abc)", &opts).1,
+            vec![
+                SyntaxError::TooLong("symbol", 3, Span::new(1, 5)),
+                SyntaxError::TooLong("string", 5, Span::new(6, 12)),
+                SyntaxError::TooLong("symbol", 3, Span::new(13, 17)),
+            ]
+        );
+        assert_eq!(
+            read_with(&"a ".repeat(13), &opts).1,
+            vec![SyntaxError::TooLong("source", 24, Span::new(24, 26))]
+        );
+    }
+
+    #[test]
+    fn interpolation() {
+        let opts = ReaderOptions::default();
+        assert_eq!(
+            read_str(
+                r#"#"Hello ~(name), ~(count n) items \~ ~((f) "~(")""#,
+                &opts
+            ),
+            "(format \"Hello ~a, ~a items ~~ ~a\" name (count n) ((f) \"~(\"))\n"
+        );
+        let (root, _) = read_with(r#"#"a~(x)b""#, &opts);
+        let root = root.unwrap();
+        let SexprKind::List(list) = root.sexprs[0].kind.as_ref() else {
+            panic!("expected a list");
+        };
+        assert_eq!(list.iter().nth(2).unwrap().span, Span::from(5..6u32));
+        // escapes are pinned down in the text around the expressions
+        match &read_with(r#"#"~(x) a\qb""#, &opts).1[..] {
+            [SyntaxError::ParseError(_, span)] => assert_eq!(*span, Span::new(8, 10)),
+            errs => panic!("expected one error, got {:?}", errs),
+        }
+        // expressions are read with the options of the string around them
+        assert_eq!(
+            read_str("#!fold-case #\"~(Foo X)\"", &opts),
+            "(format \"~a\" (foo x))\n"
+        );
+        let mut opts = ReaderOptions {
+            case_insensitive: true,
+            extensions: Extensions {
+                list_literals: false,
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        opts.aliases.insert("≤".into(), "<=".into());
+        assert_eq!(
+            read_str("#\"~(≤ [X] 1)\"", &opts),
+            "(format \"~a\" (<= (x) 1))\n"
+        );
+    }
+
+    #[test]
+    fn radix() {
+        let opts = ReaderOptions::default();
+        assert_eq!(
+            read_str("#xff #XFF #o755 #b1010 0x10 0o10 0b10", &opts),
+            "255\n255\n493\n10\n16\n8\n2\n"
+        );
+        #[cfg(feature = "bignum")]
+        {
+            let (root, errs) = read_with("#xffffffffffffffff", &opts);
+            assert_eq!(errs, vec![]);
+            let root = root.unwrap();
+            match root.sexprs[0].kind.as_ref() {
+                SexprKind::Atom(atom) => match atom.kind.as_ref() {
+                    AtomKind::Lit(Lit::BigInt(n)) => {
+                        assert_eq!(n.to_string(), "18446744073709551615")
+                    }
+                    kind => panic!("expected a bigint, got {:?}", kind),
+                },
+                kind => panic!("expected an atom, got {:?}", kind),
+            }
+        }
+    }
+
+    #[test]
+    fn reals() {
+        let opts = ReaderOptions::default();
+        assert_eq!(
+            read_str("1e3 2.5E-3 +inf.0 -inf.0 +nan.0 -nan.0 +inf", &opts),
+            "1000.0\n0.0025\n+inf.0\n-inf.0\n+nan.0\n+nan.0\n+inf\n"
+        );
+    }
+
+    #[test]
+    fn signed() {
+        let opts = ReaderOptions::default();
+        assert_eq!(
+            read_str("(- 5) -42 +7 -1.5 +3/4 -2.5E-3 - -> -x", &opts),
+            "(- 5)\n-42\n7\n-1.5\n3/4\n-0.0025\n-\n->\n-x\n"
+        );
+        let (root, _) = read_with("-42 -x", &opts);
+        let kinds = root
+            .unwrap()
+            .sexprs
+            .iter()
+            .map(|sexpr| match sexpr.kind.as_ref() {
+                SexprKind::Atom(atom) => matches!(atom.kind.as_ref(), AtomKind::Lit(_)),
+                _ => false,
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(kinds, vec![true, false]);
+    }
+
+    #[test]
+    fn lex_recovery() {
+        let opts = ReaderOptions::default();
+        let (root, errs) = read_with(r#"(a #\nosuchchar b) (c "\q")"#, &opts);
+        assert_eq!(root.unwrap().to_string(), "(a b)\n(c \"\\\\q\")\n");
+        assert_eq!(
+            errs,
+            vec![
+                SyntaxError::LexError(Span::new(3, 15)),
+                SyntaxError::InvalidEscape(Span::new(23, 25))
+            ]
+        );
+    }
+
+    #[test]
+    fn parse_recovery() {
+        let opts = ReaderOptions::default();
+        let (root, errs) = read_with("(a . b c) (d [e . f]) (g)) (h", &opts);
+        assert_eq!(root.unwrap().to_string(), "()\n(d ())\n(g)\nh\n");
+        assert!(errs.len() >= 3, "{:?}", errs);
+        assert!(errs
+            .iter()
+            .all(|err| matches!(err, SyntaxError::ParseError(..))));
+    }
+
+    #[test]
+    fn deep_nesting() {
+        let span = Span::new(0, 0);
+        let mut sexpr = Sexpr::new(SexprKind::List(List::Empty), span);
+        for _ in 0..100_000 {
+            sexpr = Sexpr::new(SexprKind::List(List::from(vec![sexpr])), span);
+        }
+        let printed = sexpr.to_string();
+        assert_eq!(printed.len(), 200_002);
+        assert!(printed.starts_with("((((") && printed.ends_with("))))"));
+        assert!(sexpr.clone() == sexpr);
+        drop(sexpr);
+
+        let atom = Sexpr::new(
+            SexprKind::Atom(Atom::new(AtomKind::Sym(InternedString::from("a")), span)),
+            span,
+        );
+        let list = List::from(vec![atom; 1_000_000]);
+        assert!(list.clone() == list);
+        assert_eq!(list.iter().count(), 1_000_000);
+        drop(list);
+    }
+
+    #[test]
+    fn single_datum() {
+        assert_eq!(parse_datum(" (1 2 3) ").unwrap().to_string(), "(1 2 3)");
+        assert_eq!(
+            parse_datum("a b"),
+            Err(vec![SyntaxError::ParseError(
+                "expected only one datum".to_string(),
+                Span::from(2..3u32)
+            )])
+        );
+        assert!(parse_datum("; nothing").is_err());
+        assert!(parse_datum("(a").is_err());
     }
 }
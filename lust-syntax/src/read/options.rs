@@ -0,0 +1,116 @@
+use super::{macros::ReaderMacros, wisp::Syntax};
+use lust_utils::{intern::InternedString, span::FileId};
+use std::collections::HashMap;
+
+#[derive(Debug, Clone)]
+pub struct ReaderOptions {
+    // fold symbols to lower case, as if the source began with #!fold-case
+    pub case_insensitive: bool,
+    // symbols that are replaced by another symbol when they're interned
+    pub aliases: HashMap<InternedString, InternedString>,
+    // surface syntax of the source
+    pub syntax: Syntax,
+    // what to do with int literals that don't fit in 64 bits
+    pub int_mode: IntMode,
+    // `#name datum` forms added by the embedder
+    pub macros: ReaderMacros,
+    pub limits: Limits,
+    pub extensions: Extensions,
+    // the file the spans of everything read are in, from a SourceCache
+    pub file: FileId,
+}
+
+// Lust's additions to classic s-expressions, all on by default. With one
+// off its syntax is read the classic way where there is one, and reported
+// as an error where there isn't.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Extensions {
+    // [a b] as (list a b), or else as (a b)
+    pub list_literals: bool,
+    // {k v} as (map k v)
+    pub maps: bool,
+    // #{a b} as (set a b)
+    pub sets: bool,
+    // #[a b]
+    pub vectors: bool,
+    // #"~(x)" strings
+    pub interpolation: bool,
+    // a.b as a path, or else the symbol a.b
+    pub paths: bool,
+    // foo... as (varg foo), or else the symbol foo...
+    pub variadic: bool,
+    // :name as a keyword, or else the symbol :name
+    pub keywords: bool,
+}
+
+impl Extensions {
+    // Plain s-expressions, for embedders that want nothing else.
+    pub fn none() -> Self {
+        Self {
+            list_literals: false,
+            maps: false,
+            sets: false,
+            vectors: false,
+            interpolation: false,
+            paths: false,
+            variadic: false,
+            keywords: false,
+        }
+    }
+}
+
+impl Default for Extensions {
+    fn default() -> Self {
+        Self {
+            list_literals: true,
+            maps: true,
+            sets: true,
+            vectors: true,
+            interpolation: true,
+            paths: true,
+            variadic: true,
+            keywords: true,
+        }
+    }
+}
+
+// Sizes past which input is rejected, in bytes of source. None is no
+// limit.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Limits {
+    pub max_source_len: Option<usize>,
+    // including the quotes
+    pub max_string_len: Option<usize>,
+    pub max_symbol_len: Option<usize>,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum IntMode {
+    // read them as BigInts
+    #[cfg(feature = "bignum")]
+    #[default]
+    Big,
+    // report them as errors
+    #[cfg_attr(not(feature = "bignum"), default)]
+    Checked,
+    // wrap them around to an i64
+    Wrapping,
+}
+
+impl Default for ReaderOptions {
+    fn default() -> Self {
+        Self {
+            case_insensitive: false,
+            aliases: [("λ", "fn"), ("→", "->")]
+                .into_iter()
+                .map(|(from, to)| (InternedString::from(from), InternedString::from(to)))
+                .collect(),
+            syntax: Syntax::Sexpr,
+            int_mode: IntMode::default(),
+            macros: ReaderMacros::default(),
+            limits: Limits::default(),
+            extensions: Extensions::default(),
+            file: FileId::default(),
+        }
+    }
+}
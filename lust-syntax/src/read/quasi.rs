@@ -0,0 +1,143 @@
+/*
+ * Quasiquote levels. Each quasiquote goes a level deeper and each unquote
+ * comes back out one, so in
+ *
+ *     `(a `(b ,(c ,d)))
+ *
+ * ,(c ,d) is still data, one level in, and only d is evaluated. The reader
+ * reads all of them as plain lists, this works out the level of each one
+ * so an expander doesn't have to.
+ */
+use super::sexpr::{AtomKind, Sexpr, SexprKind};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum QuasiKind {
+    Quasiquote,
+    Unquote,
+    UnquoteSplicing,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct QuasiForm<'a> {
+    pub kind: QuasiKind,
+    // the whole form, like (unquote x)
+    pub sexpr: &'a Sexpr,
+    // what's quoted or unquoted, the x
+    pub datum: &'a Sexpr,
+    // the level the datum is at, so an unquote's datum at level 0 is
+    // evaluated
+    pub level: u32,
+}
+
+impl Sexpr {
+    // Every quasiquote in the sexpr and every unquote inside one, in source
+    // order. Unquotes that aren't in a quasiquote are left out, and so is
+    // anything quoted outside one.
+    pub fn quasi_forms(&self) -> Vec<QuasiForm<'_>> {
+        let mut forms = vec![];
+        // walked with an explicit stack, like label::check_datum
+        let mut stack = vec![(self, 0)];
+        while let Some((sexpr, level)) = stack.pop() {
+            let items: Vec<&Sexpr> = match sexpr.kind.as_ref() {
+                SexprKind::List(list) => list.iter().collect(),
+                SexprKind::DottedList(list, tail) => {
+                    list.iter().chain(std::iter::once(tail)).collect()
+                }
+                SexprKind::DatumLabel(_, datum) => vec![datum],
+                SexprKind::Atom(_) | SexprKind::DatumRef(_) => vec![],
+            };
+            let head = match (sexpr.kind.as_ref(), &items[..]) {
+                (SexprKind::List(_), [head, datum]) => head_sym(head).map(|name| (name, *datum)),
+                _ => None,
+            };
+            let (kind, datum, level) = match head {
+                Some(("quasiquote", datum)) => (QuasiKind::Quasiquote, datum, level + 1),
+                Some(("unquote", datum)) if level > 0 => (QuasiKind::Unquote, datum, level - 1),
+                Some(("unquote-splicing", datum)) if level > 0 => {
+                    (QuasiKind::UnquoteSplicing, datum, level - 1)
+                }
+                // '`x is a quoted list, not a quasiquote
+                Some(("quote", _)) if level == 0 => continue,
+                _ => {
+                    stack.extend(items.into_iter().rev().map(|item| (item, level)));
+                    continue;
+                }
+            };
+            forms.push(QuasiForm {
+                kind,
+                sexpr,
+                datum,
+                level,
+            });
+            stack.push((datum, level));
+        }
+        forms
+    }
+}
+
+fn head_sym(sexpr: &Sexpr) -> Option<&str> {
+    match sexpr.kind.as_ref() {
+        SexprKind::Atom(atom) => match atom.kind.as_ref() {
+            AtomKind::Sym(name) => Some(&**name),
+            _ => None,
+        },
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::QuasiKind::{self, *};
+    use crate::read::parse_datum;
+
+    fn levels(src: &str) -> Vec<(QuasiKind, String, u32)> {
+        parse_datum(src)
+            .unwrap()
+            .quasi_forms()
+            .iter()
+            .map(|form| (form.kind, form.datum.to_string(), form.level))
+            .collect()
+    }
+
+    #[test]
+    fn nested() {
+        assert_eq!(
+            levels("`(a `(b ,(c ,d)))"),
+            vec![
+                (
+                    Quasiquote,
+                    "(a (quasiquote (b (unquote (c (unquote d))))))".into(),
+                    1
+                ),
+                (Quasiquote, "(b (unquote (c (unquote d))))".into(), 2),
+                (Unquote, "(c (unquote d))".into(), 1),
+                (Unquote, "d".into(), 0),
+            ]
+        );
+        // a quasiquote in evaluated code starts again from level 1
+        assert_eq!(
+            levels("`(a ,@(f `(b ,c)))")
+                .into_iter()
+                .map(|(kind, _, level)| (kind, level))
+                .collect::<Vec<_>>(),
+            vec![
+                (Quasiquote, 1),
+                (UnquoteSplicing, 0),
+                (Quasiquote, 1),
+                (Unquote, 0)
+            ]
+        );
+    }
+
+    #[test]
+    fn outside_quasiquote() {
+        assert_eq!(levels("(f ,x '`(,y))"), vec![]);
+        assert_eq!(
+            levels("`'(,x)"),
+            vec![
+                (Quasiquote, "(quote ((unquote x)))".into(), 1),
+                (Unquote, "x".into(), 0),
+            ]
+        );
+    }
+}
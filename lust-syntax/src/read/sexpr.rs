@@ -1,8 +1,11 @@
+use super::escape::{escape, escape_symbol};
+#[cfg(feature = "bignum")]
+use lust_utils::num::{BigInt, BigRational};
 use lust_utils::{
     intern::InternedString,
     list::List,
-    num::{BigInt, BigRational, Int, Rational, Real},
-    span::Span,
+    num::{Complex, Int, Rational, Real},
+    span::{FileId, Span},
 };
 use std::fmt::Display;
 
@@ -10,10 +13,38 @@ use std::fmt::Display;
 pub struct Root {
     pub sexprs: Vec<Sexpr>,
     pub span: Span,
+    // dialect named by a leading `#lang` line
+    pub lang: Option<InternedString>,
+}
+
+impl Root {
+    pub fn new(sexprs: Vec<Sexpr>, span: Span) -> Self {
+        Self {
+            sexprs,
+            span,
+            lang: None,
+        }
+    }
+
+    // a copy with every span in `file`
+    pub fn in_file(&self, file: FileId) -> Root {
+        Root {
+            sexprs: self
+                .sexprs
+                .iter()
+                .map(|sexpr| sexpr.in_file(file))
+                .collect(),
+            span: self.span.in_file(file),
+            lang: self.lang,
+        }
+    }
 }
 
 impl Display for Root {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if let Some(lang) = &self.lang {
+            writeln!(f, "#lang {}", lang)?;
+        }
         for s in &self.sexprs {
             writeln!(f, "{}", s)?;
         }
@@ -21,7 +52,7 @@ impl Display for Root {
     }
 }
 
-#[derive(Debug, Clone, PartialEq, PartialOrd)]
+#[derive(Debug, PartialOrd)]
 pub struct Sexpr {
     pub kind: Box<SexprKind>,
     pub span: Span,
@@ -36,10 +67,10 @@ impl Sexpr {
     }
 
     pub fn as_special_form(&self) -> Option<&str> {
-        match *self.kind {
+        match self.kind.as_ref() {
             SexprKind::List(l) => match l.head() {
-                Some(head) => match *head.kind {
-                    SexprKind::Atom(a) => match *a.kind {
+                Some(head) => match head.kind.as_ref() {
+                    SexprKind::Atom(a) => match a.kind.as_ref() {
                         AtomKind::Sym(s) => match s.as_ref() {
                             "def" | "let" | "quote" | "fn" | "and" | "or" | "match"
                             | "quasiquote" => Some(s.as_ref()),
@@ -56,45 +87,118 @@ impl Sexpr {
     }
 
     pub fn as_atom(&self) -> Option<Atom> {
-        match *self.kind {
-            SexprKind::Atom(a) => Some(a),
+        match self.kind.as_ref() {
+            SexprKind::Atom(a) => Some(a.clone()),
             _ => None,
         }
     }
 
     pub fn as_list(&self) -> Option<List<Sexpr>> {
-        match *self.kind {
-            SexprKind::List(l) => Some(l),
+        match self.kind.as_ref() {
+            SexprKind::List(l) => Some(l.clone()),
             _ => None,
         }
     }
 
+    // Sexpr implements Drop, so its kind can't be moved out of it directly
+    pub fn into_kind(mut self) -> SexprKind {
+        std::mem::replace(self.kind.as_mut(), SexprKind::DatumRef(0))
+    }
+
     pub fn replace(&mut self, kind: SexprKind) {
-        self.kind = Box::new(kind);
+        *self.kind = kind;
     }
 
     pub fn replace_sym(&mut self, sym: InternedString, arg: Sexpr) {
         // recursively replace all instances of the symbol
-        match *self.kind {
-            SexprKind::Atom(a) => match *a.kind {
-                AtomKind::Sym(s) => {
-                    if s == sym {
-                        *self = arg;
-                    }
+        match self.kind.as_mut() {
+            SexprKind::Atom(a) => {
+                if matches!(a.kind.as_ref(), AtomKind::Sym(s) if *s == sym) {
+                    *self = arg;
                 }
-                _ => (),
-            },
+            }
             SexprKind::List(l) => {
                 let mut new_vec = vec![];
                 for s in l.iter() {
                     let mut new_s = s.clone();
-                    new_s.replace_sym(sym.clone(), arg.clone());
+                    new_s.replace_sym(sym, arg.clone());
                     new_vec.push(new_s);
                 }
                 let new_list = List::from(new_vec);
                 *self = Sexpr::new(SexprKind::List(new_list), self.span);
             }
+            SexprKind::DottedList(list, tail) => {
+                let mut new_vec = vec![];
+                for s in list.iter() {
+                    let mut new_s = s.clone();
+                    new_s.replace_sym(sym, arg.clone());
+                    new_vec.push(new_s);
+                }
+                tail.replace_sym(sym, arg);
+                *list = List::from(new_vec);
+            }
+            SexprKind::DatumLabel(_, sexpr) => sexpr.replace_sym(sym, arg),
+            SexprKind::DatumRef(_) => (),
+        }
+    }
+
+    // a copy with every span shifted by `offset`
+    pub fn shift(&self, offset: u32) -> Sexpr {
+        self.map_spans(&|span| span.shift(offset))
+    }
+
+    // a copy with every span in `file`
+    pub fn in_file(&self, file: FileId) -> Sexpr {
+        self.map_spans(&|span| span.in_file(file))
+    }
+
+    // Built bottom up from an explicit stack, for the same reason as Drop:
+    // each node is visited, then rebuilt once its children are done.
+    fn map_spans(&self, f: &impl Fn(Span) -> Span) -> Sexpr {
+        enum Task<'a> {
+            Visit(&'a Sexpr),
+            Build(&'a Sexpr),
         }
+        let mut tasks = vec![Task::Visit(self)];
+        let mut done = vec![];
+        while let Some(task) = tasks.pop() {
+            match task {
+                Task::Visit(sexpr) => {
+                    tasks.push(Task::Build(sexpr));
+                    let children = match sexpr.kind.as_ref() {
+                        SexprKind::List(list) => list.iter().collect(),
+                        SexprKind::DottedList(list, tail) => {
+                            list.iter().chain(std::iter::once(tail)).collect()
+                        }
+                        SexprKind::DatumLabel(_, sexpr) => vec![sexpr],
+                        SexprKind::Atom(_) | SexprKind::DatumRef(_) => vec![],
+                    };
+                    tasks.extend(children.into_iter().rev().map(Task::Visit));
+                }
+                Task::Build(sexpr) => {
+                    let kind = match sexpr.kind.as_ref() {
+                        SexprKind::Atom(atom) => {
+                            SexprKind::Atom(Atom::new(atom.kind.as_ref().clone(), f(atom.span)))
+                        }
+                        SexprKind::List(list) => {
+                            let items = done.split_off(done.len() - list.iter().count());
+                            SexprKind::List(List::from(items))
+                        }
+                        SexprKind::DottedList(list, _) => {
+                            let tail = done.pop().unwrap();
+                            let items = done.split_off(done.len() - list.iter().count());
+                            SexprKind::DottedList(List::from(items), tail)
+                        }
+                        SexprKind::DatumLabel(n, _) => {
+                            SexprKind::DatumLabel(*n, done.pop().unwrap())
+                        }
+                        SexprKind::DatumRef(n) => SexprKind::DatumRef(*n),
+                    };
+                    done.push(Sexpr::new(kind, f(sexpr.span)));
+                }
+            }
+        }
+        done.pop().unwrap()
     }
 }
 
@@ -104,20 +208,137 @@ impl Display for Sexpr {
     }
 }
 
+// Children are moved onto a stack and dropped one at a time, so dropping
+// deeply nested or very long data can't overflow the Rust stack.
+impl Drop for Sexpr {
+    fn drop(&mut self) {
+        let mut stack = vec![];
+        let mut kind = std::mem::replace(self.kind.as_mut(), SexprKind::DatumRef(0));
+        loop {
+            match kind {
+                SexprKind::List(mut list) => {
+                    while let Some(item) = list.pop_front() {
+                        stack.push(item);
+                    }
+                }
+                SexprKind::DottedList(mut list, tail) => {
+                    while let Some(item) = list.pop_front() {
+                        stack.push(item);
+                    }
+                    stack.push(tail);
+                }
+                SexprKind::DatumLabel(_, sexpr) => stack.push(sexpr),
+                SexprKind::Atom(_) | SexprKind::DatumRef(_) => (),
+            }
+            match stack.pop() {
+                Some(sexpr) => kind = sexpr.into_kind(),
+                None => break,
+            }
+        }
+    }
+}
+
+impl Clone for Sexpr {
+    fn clone(&self) -> Self {
+        self.map_spans(&|span| span)
+    }
+}
+
+// Compares pairs of children from a stack rather than recursively.
+impl PartialEq for Sexpr {
+    fn eq(&self, other: &Self) -> bool {
+        let mut stack = vec![(self, other)];
+        while let Some((a, b)) = stack.pop() {
+            if a.span != b.span {
+                return false;
+            }
+            match (a.kind.as_ref(), b.kind.as_ref()) {
+                (SexprKind::Atom(a), SexprKind::Atom(b)) if a == b => (),
+                (SexprKind::List(a), SexprKind::List(b))
+                    if a.iter().count() == b.iter().count() =>
+                {
+                    stack.extend(a.iter().zip(b.iter()));
+                }
+                (SexprKind::DottedList(a, a_tail), SexprKind::DottedList(b, b_tail))
+                    if a.iter().count() == b.iter().count() =>
+                {
+                    stack.extend(a.iter().zip(b.iter()));
+                    stack.push((a_tail, b_tail));
+                }
+                (SexprKind::DatumLabel(m, a), SexprKind::DatumLabel(n, b)) if m == n => {
+                    stack.push((a, b));
+                }
+                (SexprKind::DatumRef(m), SexprKind::DatumRef(n)) if m == n => (),
+                _ => return false,
+            }
+        }
+        true
+    }
+}
+
 impl Eq for Sexpr {}
 
 #[derive(Debug, Clone, PartialEq, PartialOrd)]
 pub enum SexprKind {
     Atom(Atom),
     List(List<Sexpr>),
+    // (a b . c), the items before the dot and the tail after it
+    DottedList(List<Sexpr>, Sexpr),
+    // #0=datum
+    DatumLabel(u32, Sexpr),
+    // #0#
+    DatumRef(u32),
 }
 
+// Printed from an explicit stack of what's left to write rather than
+// recursively, for the same reason as Sexpr's Drop.
 impl Display for SexprKind {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        match self {
-            SexprKind::Atom(a) => write!(f, "{}", a),
-            SexprKind::List(l) => write!(f, "{}", l),
+        enum Item<'a> {
+            Kind(&'a SexprKind),
+            Text(&'static str),
         }
+        let mut stack = vec![Item::Kind(self)];
+        while let Some(item) = stack.pop() {
+            let kind = match item {
+                Item::Kind(kind) => kind,
+                Item::Text(text) => {
+                    f.write_str(text)?;
+                    continue;
+                }
+            };
+            match kind {
+                SexprKind::Atom(a) => write!(f, "{}", a)?,
+                SexprKind::List(l) => {
+                    f.write_str("(")?;
+                    stack.push(Item::Text(")"));
+                    let items = l.iter().collect::<Vec<_>>();
+                    for (i, s) in items.iter().enumerate().rev() {
+                        stack.push(Item::Kind(&s.kind));
+                        if i != 0 {
+                            stack.push(Item::Text(" "));
+                        }
+                    }
+                }
+                SexprKind::DottedList(l, tail) => {
+                    f.write_str("(")?;
+                    stack.push(Item::Text(")"));
+                    stack.push(Item::Kind(&tail.kind));
+                    stack.push(Item::Text(". "));
+                    let items = l.iter().collect::<Vec<_>>();
+                    for s in items.iter().rev() {
+                        stack.push(Item::Text(" "));
+                        stack.push(Item::Kind(&s.kind));
+                    }
+                }
+                SexprKind::DatumLabel(n, s) => {
+                    write!(f, "#{}=", n)?;
+                    stack.push(Item::Kind(&s.kind));
+                }
+                SexprKind::DatumRef(n) => write!(f, "#{}#", n)?,
+            }
+        }
+        Ok(())
     }
 }
 
@@ -136,18 +357,26 @@ impl Atom {
     }
 
     pub fn as_lit(&self) -> Option<Lit> {
-        match *self.kind {
-            AtomKind::Lit(l) => Some(l),
+        match self.kind.as_ref() {
+            AtomKind::Lit(l) => Some(l.clone()),
             _ => None,
         }
     }
 
     pub fn as_sym(&self) -> Option<InternedString> {
-        match *self.kind {
-            AtomKind::Sym(s) => Some(s),
+        match self.kind.as_ref() {
+            AtomKind::Sym(s) => Some(*s),
             _ => None,
         }
     }
+
+    // The atom as written in `src`, so a string literal keeps its quotes
+    // and escapes while `Lit::String` holds the decoded text. Atoms the
+    // reader makes up, like the `format` of an interpolated string, give
+    // the text of the form they came from.
+    pub fn source<'src>(&self, src: &'src str) -> &'src str {
+        &src[self.span]
+    }
 }
 
 impl Display for Atom {
@@ -160,13 +389,27 @@ impl Display for Atom {
 pub enum AtomKind {
     Lit(Lit),
     Sym(InternedString),
+    // :name, stored without the colon
+    Keyword(InternedString),
+    // a.b.c
+    Path(Vec<InternedString>),
 }
 
 impl Display for AtomKind {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
             AtomKind::Lit(l) => write!(f, "{}", l),
-            AtomKind::Sym(s) => write!(f, "{}", s),
+            AtomKind::Sym(s) => write!(f, "{}", escape_symbol(s)),
+            AtomKind::Keyword(s) => write!(f, ":{}", s),
+            AtomKind::Path(names) => {
+                for (i, name) in names.iter().enumerate() {
+                    if i != 0 {
+                        write!(f, ".")?;
+                    }
+                    write!(f, "{}", name)?;
+                }
+                Ok(())
+            }
         }
     }
 }
@@ -174,26 +417,50 @@ impl Display for AtomKind {
 #[derive(Debug, Clone, PartialEq, PartialOrd)]
 pub enum Lit {
     Int(Int),
+    #[cfg(feature = "bignum")]
     BigInt(BigInt),
     Real(Real),
     Rational(Rational),
+    #[cfg(feature = "bignum")]
     BigRational(BigRational),
+    Complex(Complex),
     String(InternedString),
     Bool(bool),
     Char(char),
+    Bytes(Vec<u8>),
 }
 
 impl Display for Lit {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
             Lit::Int(i) => write!(f, "{}", i),
+            #[cfg(feature = "bignum")]
             Lit::BigInt(i) => write!(f, "{}", i),
             Lit::Real(r) => write!(f, "{}", r),
             Lit::Rational(r) => write!(f, "{}", r),
+            #[cfg(feature = "bignum")]
             Lit::BigRational(r) => write!(f, "{}", r),
-            Lit::String(s) => write!(f, "{}", s),
-            Lit::Bool(b) => write!(f, "{}", b),
-            Lit::Char(c) => write!(f, "{}", c),
+            Lit::Complex(c) => write!(f, "{}", c),
+            Lit::String(s) => write!(f, "\"{}\"", escape(s)),
+            Lit::Bool(true) => write!(f, "#t"),
+            Lit::Bool(false) => write!(f, "#f"),
+            Lit::Char(c) => match *c {
+                ' ' => write!(f, "#\\space"),
+                '\n' => write!(f, "#\\newline"),
+                '\t' => write!(f, "#\\tab"),
+                c if c.is_control() || c.is_whitespace() => write!(f, "#\\x{:x}", c as u32),
+                c => write!(f, "#\\{}", c),
+            },
+            Lit::Bytes(bytes) => {
+                write!(f, "#u8(")?;
+                for (i, b) in bytes.iter().enumerate() {
+                    if i > 0 {
+                        write!(f, " ")?;
+                    }
+                    write!(f, "{}", b)?;
+                }
+                write!(f, ")")
+            }
         }
     }
 }
@@ -0,0 +1,75 @@
+use super::{options::IntMode, token::Token};
+use logos::Lexer;
+#[cfg(feature = "bignum")]
+use lust_utils::num::BigInt;
+use lust_utils::{num::Int, span::Span};
+use std::vec;
+
+// A stream of spanned tokens for the reader. Lex errors are reported as
+// `Token::Error` with the span of the offending input, except for int
+// literals that don't fit in an i64, which are handled by the `IntMode`.
+// `Token::String` holds the raw source text, quotes included and escapes
+// undecoded, the way the lexer produces it. The reader decodes it and
+// reports a string that isn't quoted as a lex error.
+pub trait TokenSource {
+    fn next_token(&mut self) -> Option<(Token, Span)>;
+}
+
+impl<'src> TokenSource for Lexer<'src, Token> {
+    fn next_token(&mut self) -> Option<(Token, Span)> {
+        let tok = match self.next()? {
+            Ok(tok) => tok,
+            Err(_) => match int_digits(self.slice()) {
+                Some((radix, digits)) => overflow(digits, radix, self.extras.int_mode),
+                None => Token::Error,
+            },
+        };
+        Some((tok, Span::from(self.span())))
+    }
+}
+
+// The radix and digits of an int literal. Decimal literals keep their
+// sign, radix prefixed ones (#x, 0x, #o, 0o, #b, 0b) have none.
+fn int_digits(s: &str) -> Option<(u32, &str)> {
+    let (radix, digits) = match s.as_bytes() {
+        [b'0' | b'#', b'x' | b'X', ..] => (16, &s[2..]),
+        [b'0' | b'#', b'o' | b'O', ..] => (8, &s[2..]),
+        [b'0' | b'#', b'b' | b'B', ..] => (2, &s[2..]),
+        _ => (10, s),
+    };
+    let unsigned = if radix == 10 {
+        digits.strip_prefix(['+', '-']).unwrap_or(digits)
+    } else {
+        digits
+    };
+    (!unsigned.is_empty() && unsigned.chars().all(|c| c.is_digit(radix))).then_some((radix, digits))
+}
+
+fn overflow(digits: &str, radix: u32, mode: IntMode) -> Token {
+    match mode {
+        // the token holds decimal text whatever the literal's radix
+        #[cfg(feature = "bignum")]
+        IntMode::Big => match BigInt::from_str_radix(digits, radix) {
+            Some(n) => Token::BigInt(n.to_string().into()),
+            None => Token::Error,
+        },
+        IntMode::Checked => Token::IntOverflow,
+        IntMode::Wrapping => {
+            let (neg, digits) = match digits.strip_prefix('-') {
+                Some(digits) => (true, digits),
+                None => (false, digits.trim_start_matches('+')),
+            };
+            let n = digits.chars().fold(0i64, |n, d| {
+                n.wrapping_mul(radix as i64)
+                    .wrapping_add(d.to_digit(radix).unwrap() as i64)
+            });
+            Token::Int(Int::new(if neg { n.wrapping_neg() } else { n }))
+        }
+    }
+}
+
+impl TokenSource for vec::IntoIter<(Token, Span)> {
+    fn next_token(&mut self) -> Option<(Token, Span)> {
+        self.next()
+    }
+}
@@ -0,0 +1,151 @@
+/*
+ * Reading from a stream one datum at a time. Lines are read until the
+ * buffer holds a complete datum, so each datum has to fit in memory but the
+ * whole input doesn't. Spans are offsets into the whole stream.
+ *
+ * More lines are only read while the datum is still open, with an error at
+ * the end of the buffer like an unclosed list, string or block comment.
+ * Any other error is reported and skipped, and reading carries on after it.
+ *
+ * A wisp datum isn't complete until the next unindented line, so wisp
+ * input is read to the end before anything is returned. Spans are 32 bit,
+ * so a stream stops with an error once it passes 4 GiB.
+ */
+use super::{
+    error::SyntaxError, options::ReaderOptions, read_one_with, sexpr::Sexpr, wisp::Syntax,
+};
+use std::io::{self, BufRead};
+
+pub struct StreamReader<R> {
+    input: R,
+    opts: ReaderOptions,
+    buf: String,
+    // where buf starts in the stream
+    offset: u64,
+    eof: bool,
+}
+
+pub fn read_from<R: BufRead>(input: R) -> StreamReader<R> {
+    read_from_with(input, ReaderOptions::default())
+}
+
+pub fn read_from_with<R: BufRead>(input: R, opts: ReaderOptions) -> StreamReader<R> {
+    StreamReader {
+        input,
+        opts,
+        buf: String::new(),
+        offset: 0,
+        eof: false,
+    }
+}
+
+impl<R: BufRead> Iterator for StreamReader<R> {
+    type Item = io::Result<Result<Sexpr, Vec<SyntaxError>>>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            let Ok(offset) = u32::try_from(self.offset + self.buf.len() as u64) else {
+                self.eof = true;
+                self.buf.clear();
+                return Some(Err(io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    "stream too long for 32 bit spans",
+                )));
+            };
+            let offset = offset - self.buf.len() as u32;
+            let complete = self.eof || self.opts.syntax == Syntax::Sexpr;
+            let (sexpr, rest, errs) = read_one_with(&self.buf, &self.opts);
+            let used = self.buf.len() - rest.len();
+            // an error at the end of input can have its start past its end
+            let end = |err: &SyntaxError| err.span().start().max(err.span().end()) as usize;
+            let open = errs.iter().any(|err| end(err) >= self.buf.len());
+            match sexpr {
+                Some(sexpr) if complete => {
+                    let sexpr = sexpr.shift(offset);
+                    self.consume(used);
+                    return Some(Ok(Ok(sexpr)));
+                }
+                None if errs.is_empty() && self.eof => return None,
+                // nothing but whitespace and comments
+                None if errs.is_empty() => self.consume(self.buf.len()),
+                None if self.eof || (complete && !open) => {
+                    // past the malformed datum, or the error if there's none
+                    let skip = match used {
+                        0 => errs.iter().map(end).max().unwrap_or(self.buf.len()),
+                        used => used,
+                    };
+                    let errs = errs.into_iter().map(|err| err.shift(offset)).collect();
+                    self.consume(skip.clamp(1, self.buf.len()));
+                    return Some(Ok(Err(errs)));
+                }
+                // the datum may be incomplete
+                _ => (),
+            }
+            match self.input.read_line(&mut self.buf) {
+                Ok(0) => self.eof = true,
+                Ok(_) => (),
+                Err(err) => return Some(Err(err)),
+            }
+        }
+    }
+}
+
+impl<R> StreamReader<R> {
+    fn consume(&mut self, len: usize) {
+        self.buf.drain(..len);
+        self.offset += len as u64;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::read_from;
+    use crate::read::error::SyntaxError;
+    use lust_utils::span::Span;
+
+    #[test]
+    fn datums() {
+        let input = "(a\n b) c ; x\n#| y\n|# \"d\ne\"\n".as_bytes();
+        let sexprs = read_from(input)
+            .map(|res| res.unwrap().unwrap())
+            .map(|sexpr| (sexpr.to_string(), sexpr.span))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            sexprs,
+            vec![
+                ("(a b)".to_string(), Span::new(0, 6)),
+                ("c".to_string(), Span::new(7, 8)),
+                ("\"d\\ne\"".to_string(), Span::new(21, 26)),
+            ]
+        );
+    }
+
+    #[test]
+    fn incomplete() {
+        let mut reader = read_from("a\n(b".as_bytes());
+        assert_eq!(reader.next().unwrap().unwrap().unwrap().to_string(), "a");
+        match reader.next().unwrap().unwrap() {
+            Err(errs) => assert!(matches!(
+                errs[..],
+                [SyntaxError::ParseError(_, span)] if span == Span::new(4, 4)
+            )),
+            Ok(sexpr) => panic!("expected an error, got {}", sexpr),
+        }
+        assert!(reader.next().is_none());
+    }
+
+    #[test]
+    fn skips_errors() {
+        let input = ")\n(a\n b) (c #u8(256)) d\n\"e\nf\" #|\n|# g".as_bytes();
+        let items = read_from(input)
+            .map(|res| match res.unwrap() {
+                Ok(sexpr) => sexpr.to_string(),
+                Err(errs) => format!("{:?}", errs[0].span()),
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(
+            items,
+            vec!["0..1", "(a b)", "16..19", "d", "\"e\\nf\"", "g"]
+        );
+    }
+}
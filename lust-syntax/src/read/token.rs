@@ -1,8 +1,25 @@
-use logos::Logos;
-use lust_utils::{intern::InternedString, num::{Int, Rational, Real}};
-use std::fmt::{Debug, Display};
+use super::{escape, interp, options::IntMode};
+use logos::{Filter, Lexer, Logos, Skip};
+use lust_utils::{
+    intern::InternedString,
+    num::{Complex, Int, Rational, Real},
+};
+use std::{
+    borrow::Cow,
+    collections::HashMap,
+    fmt::{Debug, Display},
+};
+use unicode_normalization::{is_nfc_quick, IsNormalized, UnicodeNormalization};
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct LexerState {
+    pub fold_case: bool,
+    pub aliases: HashMap<InternedString, InternedString>,
+    pub int_mode: IntMode,
+}
 
 #[derive(Logos, Debug, Clone, Default, PartialEq)]
+#[logos(extras = LexerState)]
 pub enum Token {
     Eof,
     #[default]
@@ -10,31 +27,85 @@ pub enum Token {
     #[regex(r"[ \t\r\n\f]+", logos::skip)]
     Whitespace,
     #[regex(r#";[^\n]*"#)]
+    // a #!/usr/bin/env lust line, only at the start of the file
+    #[regex(r"#![/ ][^\n]*", |lex| lex.span().start == 0)]
     Comment,
-    #[regex(r#"[^.'\d\[\]()\s,{};][^.'\[\]()\s,{};]*"#, |lex| InternedString::from(lex.slice()))]
+    // #| ... |#, which nest, are skipped, so this is only emitted for one
+    // that's never closed
+    #[token("#|", block_comment)]
+    UnterminatedComment,
+    // anything up to a delimiter, so any Unicode letter or symbol can be in
+    // a name
+    #[regex(r##"[^.'`\d\[\]()\s,{};"#:][^.'`\[\]()\s,{};"]*"##, ident)]
+    // |hello world|, taken as written
+    #[regex(r"\|([^|\\]|\\.)*\|", pipe_ident)]
     Ident(InternedString),
+    // :name, without the colon, or #:name as in Racket
+    #[regex(r##":[^.'`\[\]()\s,{};"#:][^.'`\[\]()\s,{};"]*"##, keyword)]
+    #[regex(r##"#:[^.'`\[\]()\s,{};"#:][^.'`\[\]()\s,{};"]*"##, keyword)]
+    Keyword(InternedString),
+    // Decimal numbers can be signed. -42 and +3/4 also match Ident, so the
+    // number rules all have a higher priority than it.
     #[regex(
-        r#"(0b[0-1]+)|(0o[0-7]+)|(0x[0-9a-fA-F]+)|([1-9]\d*|0)"#, 
-        priority = 2, 
-        callback = |lex| lex.slice().parse::<Int>().ok()
+        r#"(#[xX][0-9a-fA-F]+)|(#[oO][0-7]+)|(#[bB][0-1]+)|(0b[0-1]+)|(0o[0-7]+)|(0x[0-9a-fA-F]+)|([+-]?([1-9]\d*|0))"#,
+        priority = 4,
+        callback = int
     )]
     Int(Int),
+    // the text of an int literal too big for an i64, see `IntMode`
+    BigInt(InternedString),
+    IntOverflow,
     #[regex(
-        r#"([1-9]\d*|0)(\.\d+)?([eE][+-]?\d+)?"#, 
-        priority = 1, 
+        r#"[+-]?([1-9]\d*|0)(\.\d+)?([eE][+-]?\d+)?"#,
+        priority = 3,
         callback = |lex| lex.slice().parse::<Real>().ok()
     )]
+    #[regex(r"[+-](inf|nan)\.0", |lex| lex.slice().parse::<Real>().ok())]
     Real(Real),
     #[regex(
-        r#"((0b[0-1]+)|(0o[0-7]+)|(0x[0-9a-fA-F]+)|([1-9]\d*|0))(/-?((0b[0-1]+)|(0o[0-7]+)|(0x[0-9a-fA-F]+)|([1-9]\d*|0)))?"#,
-        priority = 0,
+        r#"((0b[0-1]+)|(0o[0-7]+)|(0x[0-9a-fA-F]+)|([+-]?([1-9]\d*|0)))(/-?((0b[0-1]+)|(0o[0-7]+)|(0x[0-9a-fA-F]+)|([1-9]\d*|0)))?"#,
+        priority = 2,
         callback = |lex| lex.slice().parse::<Rational>().ok()
     )]
     Rational(Rational),
+    // 3+4i, -2.5i, +i or 1@0.5. Like the other numbers, +i and -i also
+    // match Ident.
+    #[regex(
+        r#"(([+-]?(([1-9]\d*|0)(\.\d+)?([eE][+-]?\d+)?)|[+-](inf|nan)\.0)?[+-]((([1-9]\d*|0)(\.\d+)?([eE][+-]?\d+)?)|(inf|nan)\.0)?i)|(([+-]?(([1-9]\d*|0)(\.\d+)?([eE][+-]?\d+)?)|[+-](inf|nan)\.0)@([+-]?(([1-9]\d*|0)(\.\d+)?([eE][+-]?\d+)?)|[+-](inf|nan)\.0))"#,
+        priority = 5,
+        callback = |lex| lex.slice().parse::<Complex>().ok()
+    )]
+    Complex(Complex),
     #[regex(r"#t|#f", |lex| lex.slice() == "#t")]
     Bool(bool),
-    #[regex(r#""("[^"\\]*(?:\\.[^"\\]*)*")""#, |lex| InternedString::from(lex.slice()))]
+    #[regex(r#""([^"\\]|\\.)*""#, |lex| InternedString::from(lex.slice()))]
     String(InternedString),
+    // #\a, #\space or #\x41
+    #[token("#\\", character)]
+    Char(char),
+    // #"text ~(expr)"
+    #[token("#\"", interp_string)]
+    InterpString(interp::Interp),
+    // #r"text" or #r#"text"#, with any number of hashes, the text between
+    // the quotes with no escapes. Written as two rules because with #r#*"
+    // the lexer doesn't fall back to # for a reader macro like #re.
+    #[token("#r\"", raw_string)]
+    #[regex(r##"#r#+""##, raw_string)]
+    RawString(InternedString),
+    #[regex(r"#\d+=", |lex| lex.slice()[1..lex.slice().len() - 1].parse::<u32>().ok())]
+    DatumLabel(u32),
+    #[regex(r"#\d+#", |lex| lex.slice()[1..lex.slice().len() - 1].parse::<u32>().ok())]
+    DatumRef(u32),
+    // #u8(0 255 16), the bytes are read as ints
+    #[token("#u8(")]
+    HashU8LParen,
+    // #; comments out the datum after it
+    #[token("#;")]
+    DatumComment,
+
+    #[token("#!fold-case", |lex| { lex.extras.fold_case = true; Skip })]
+    #[token("#!no-fold-case", |lex| { lex.extras.fold_case = false; Skip })]
+    FoldCase,
 
     #[token("(")]
     LParen,
@@ -62,12 +133,52 @@ pub enum Token {
     Hash,
     #[token("#[")]
     HashLBrack,
+    #[token("#{")]
+    HashLBrace,
     #[token("'")]
     Quote,
     #[token("`")]
     Backquote,
 }
 
+impl Token {
+    // Tokens that don't affect what's read, so a formatter can move them
+    // around freely.
+    pub fn is_trivia(&self) -> bool {
+        matches!(
+            self,
+            Token::Whitespace | Token::Comment | Token::UnterminatedComment
+        )
+    }
+
+    // The source text of tokens that are always written the same way, like
+    // `(` or `,@`. Others carry what they read, so their text is the span's.
+    pub fn text(&self) -> Option<&'static str> {
+        use Token::*;
+        Some(match self {
+            HashU8LParen => "#u8(",
+            DatumComment => "#;",
+            LParen => "(",
+            RParen => ")",
+            LBrack => "[",
+            RBrack => "]",
+            LBrace => "{",
+            RBrace => "}",
+            Colon => ":",
+            Period => ".",
+            Ellipsis => "...",
+            Comma => ",",
+            CommaAt => ",@",
+            Hash => "#",
+            HashLBrack => "#[",
+            HashLBrace => "#{",
+            Quote => "'",
+            Backquote => "`",
+            _ => return None,
+        })
+    }
+}
+
 impl Display for Token {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         use Token::*;
@@ -76,12 +187,25 @@ impl Display for Token {
             Error => write!(f, "Error"),
             Whitespace => write!(f, "Whitespace"),
             Comment => write!(f, "Comment"),
+            UnterminatedComment => write!(f, "UnterminatedComment"),
             Ident(name) => write!(f, "Ident({})", name),
+            Keyword(name) => write!(f, "Keyword({})", name),
             Int(n) => write!(f, "Int({})", n),
+            BigInt(n) => write!(f, "BigInt({})", n),
+            IntOverflow => write!(f, "IntOverflow"),
             Real(n) => write!(f, "Float({})", n),
             Rational(n) => write!(f, "Rational({})", n),
+            Complex(n) => write!(f, "Complex({})", n),
             Bool(b) => write!(f, "Bool({})", b),
             String(s) => write!(f, "String({})", s),
+            Char(c) => write!(f, "Char({:?})", c),
+            InterpString(s) => write!(f, "#\"{}\"", s.raw),
+            RawString(s) => write!(f, "RawString({})", s),
+            FoldCase => write!(f, "FoldCase"),
+            DatumLabel(n) => write!(f, "#{}=", n),
+            DatumRef(n) => write!(f, "#{}#", n),
+            HashU8LParen => write!(f, "#u8("),
+            DatumComment => write!(f, "#;"),
             LParen => write!(f, "("),
             RParen => write!(f, ")"),
             LBrack => write!(f, "["),
@@ -95,8 +219,138 @@ impl Display for Token {
             CommaAt => write!(f, ",@"),
             Hash => write!(f, "#"),
             HashLBrack => write!(f, "#["),
+            HashLBrace => write!(f, "#{{"),
             Quote => write!(f, "'"),
             Backquote => write!(f, "`"),
         }
     }
 }
+
+fn interp_string(lex: &mut Lexer<Token>) -> Option<interp::Interp> {
+    // like an unterminated raw string, an error to the end of the source
+    let Some((_, len)) = interp::split(lex.remainder()) else {
+        lex.bump(lex.remainder().len());
+        return None;
+    };
+    let raw = InternedString::from(&lex.remainder()[..len - 1]);
+    lex.bump(len);
+    Some(interp::Interp {
+        raw,
+        fold_case: lex.extras.fold_case,
+    })
+}
+
+fn raw_string(lex: &mut Lexer<Token>) -> Option<InternedString> {
+    let hashes = lex.slice().len() - "#r\"".len();
+    let close = format!("\"{}", "#".repeat(hashes));
+    // an unterminated one takes the rest of the source, which is an error
+    let Some(end) = lex.remainder().find(&close) else {
+        lex.bump(lex.remainder().len());
+        return None;
+    };
+    let raw = InternedString::from(&lex.remainder()[..end]);
+    lex.bump(end + close.len());
+    Some(raw)
+}
+
+fn block_comment(lex: &mut Lexer<Token>) -> Filter<()> {
+    let rest = lex.remainder().as_bytes();
+    let mut depth = 1;
+    let mut i = 0;
+    while i + 1 < rest.len() {
+        match &rest[i..i + 2] {
+            b"#|" => {
+                depth += 1;
+                i += 2;
+            }
+            b"|#" => {
+                depth -= 1;
+                i += 2;
+                if depth == 0 {
+                    lex.bump(i);
+                    return Filter::Skip;
+                }
+            }
+            _ => i += 1,
+        }
+    }
+    lex.bump(rest.len());
+    Filter::Emit(())
+}
+
+// Scanned by hand rather than with a regex, which logos cut off partway
+// through a multibyte character like the λ of #\λ.
+fn character(lex: &mut Lexer<Token>) -> Option<char> {
+    let rest = lex.remainder();
+    let first = rest.chars().next()?;
+    let len = if first.is_ascii_alphabetic() {
+        rest.find(|c: char| !c.is_ascii_alphanumeric())
+            .unwrap_or(rest.len())
+    } else {
+        first.len_utf8()
+    };
+    lex.bump(len);
+    let name = &lex.slice()[2..];
+    let mut chars = name.chars();
+    if let (Some(c), None) = (chars.next(), chars.next()) {
+        return Some(c);
+    }
+    match name {
+        "alarm" => Some('\u{7}'),
+        "backspace" => Some('\u{8}'),
+        "delete" => Some('\u{7f}'),
+        "escape" => Some('\u{1b}'),
+        "newline" => Some('\n'),
+        "null" => Some('\0'),
+        "return" => Some('\r'),
+        "space" => Some(' '),
+        "tab" => Some('\t'),
+        _ => name
+            .strip_prefix('x')
+            .and_then(|hex| u32::from_str_radix(hex, 16).ok())
+            .and_then(char::from_u32),
+    }
+}
+
+// #xff and 0xff are both hex, likewise for octal and binary
+fn int(lex: &mut Lexer<Token>) -> Option<Int> {
+    let slice = lex.slice();
+    let radix = match slice.as_bytes() {
+        [b'0' | b'#', b'x' | b'X', ..] => 16,
+        [b'0' | b'#', b'o' | b'O', ..] => 8,
+        [b'0' | b'#', b'b' | b'B', ..] => 2,
+        _ => return slice.parse().ok(),
+    };
+    Int::from_str_radix(&slice[2..], radix).ok()
+}
+
+fn keyword(lex: &mut Lexer<Token>) -> InternedString {
+    let slice = lex.slice();
+    let name = slice.strip_prefix("#:").unwrap_or(&slice[1..]);
+    symbol(name, lex.extras.fold_case)
+}
+
+fn pipe_ident(lex: &mut Lexer<Token>) -> InternedString {
+    let slice = lex.slice();
+    symbol(&escape::unescape_symbol(&slice[1..slice.len() - 1]), false)
+}
+
+fn ident(lex: &mut Lexer<Token>) -> InternedString {
+    let name = symbol(lex.slice(), lex.extras.fold_case);
+    lex.extras.aliases.get(&name).copied().unwrap_or(name)
+}
+
+// Names are interned in NFC, so an é written as one code point and one
+// written as an e and a combining accent are the same symbol.
+fn symbol(name: &str, fold_case: bool) -> InternedString {
+    let name = if fold_case {
+        Cow::Owned(name.to_lowercase())
+    } else {
+        Cow::Borrowed(name)
+    };
+    if is_nfc_quick(name.chars()) == IsNormalized::Yes {
+        InternedString::from(&*name)
+    } else {
+        InternedString::from(name.nfc().collect::<String>())
+    }
+}
@@ -0,0 +1,97 @@
+/*
+ * Lowers the indentation based wisp syntax (SRFI 119) to the token stream
+ * of the equivalent s-expressions. Every line opens a list that closes
+ * once a later line starts at the same or a lower indentation, so
+ *
+ *   def (fib n)
+ *     if (<= n 1) n
+ *       + (fib (- n 1)) (fib (- n 2))
+ *
+ * reads as (def (fib n) (if (<= n 1) n (+ (fib (- n 1)) (fib (- n 2))))).
+ * A line starting with `.` continues the enclosing list instead of opening
+ * a new one, and indentation is ignored inside explicit brackets.
+ */
+use super::token::Token;
+use lust_utils::span::Span;
+use std::path::Path;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Syntax {
+    #[default]
+    Sexpr,
+    Wisp,
+}
+
+impl Syntax {
+    pub fn from_path(path: &Path) -> Self {
+        match path.extension().and_then(|ext| ext.to_str()) {
+            Some("w") | Some("wisp") => Syntax::Wisp,
+            _ => Syntax::Sexpr,
+        }
+    }
+
+    pub fn from_lang(lang: &str) -> Option<Self> {
+        match lang {
+            "wisp" | "lust/wisp" => Some(Syntax::Wisp),
+            _ => None,
+        }
+    }
+}
+
+pub fn lower(src: &str, tokens: Vec<(Token, Span)>) -> Vec<(Token, Span)> {
+    let line_starts = std::iter::once(0)
+        .chain(src.match_indices('\n').map(|(i, _)| i as u32 + 1))
+        .collect::<Vec<_>>();
+    let line_of = |offset: u32| line_starts.partition_point(|&start| start <= offset) - 1;
+
+    let mut out = vec![];
+    // indentation of each open line and whether it opened a list
+    let mut stack: Vec<(u32, bool)> = vec![];
+    let mut depth = 0usize;
+    let mut line = None;
+    let mut prev_end = 0;
+    for (tok, span) in tokens {
+        if tok == Token::Comment {
+            continue;
+        }
+        let tok_line = line_of(span.start());
+        if depth == 0 && line != Some(tok_line) {
+            let indent = span.start() - line_starts[tok_line];
+            close(&mut stack, indent, prev_end, &mut out);
+            line = Some(tok_line);
+            if tok == Token::Period {
+                stack.push((indent, false));
+                continue;
+            }
+            out.push((Token::LParen, Span::new(span.start(), span.start())));
+            stack.push((indent, true));
+        }
+        line = Some(tok_line);
+        match tok {
+            Token::LParen
+            | Token::LBrack
+            | Token::HashLBrack
+            | Token::LBrace
+            | Token::HashLBrace
+            | Token::HashU8LParen => depth += 1,
+            Token::RParen | Token::RBrack | Token::RBrace => depth = depth.saturating_sub(1),
+            _ => (),
+        }
+        prev_end = span.end();
+        out.push((tok, span));
+    }
+    close(&mut stack, 0, prev_end, &mut out);
+    out
+}
+
+fn close(stack: &mut Vec<(u32, bool)>, indent: u32, end: u32, out: &mut Vec<(Token, Span)>) {
+    while let Some(&(top, opened)) = stack.last() {
+        if top < indent {
+            break;
+        }
+        stack.pop();
+        if opened {
+            out.push((Token::RParen, Span::new(end, end)));
+        }
+    }
+}
@@ -0,0 +1,61 @@
+//! Golden tests for the reader. Every `corpus/*.lust` file is read and the
+//! printed result is compared against `*.out`, and any diagnostics against
+//! `*.err` (a missing companion file means empty). Run with
+//! `UPDATE_EXPECT=1` to rebaseline.
+use lust_syntax::read::read;
+use std::{env, fs, path::Path};
+
+fn run(src: &str) -> (String, String) {
+    let (root, errs) = read(src);
+    let out = root.map(|root| root.to_string()).unwrap_or_default();
+    let err = errs.iter().map(|err| format!("{}\n", err)).collect();
+    (out, err)
+}
+
+fn check(path: &Path, actual: &str, update: bool) -> Option<String> {
+    let expected = fs::read_to_string(path).unwrap_or_default();
+    if expected == actual {
+        return None;
+    }
+    if update {
+        if actual.is_empty() {
+            fs::remove_file(path).ok();
+        } else {
+            fs::write(path, actual).unwrap();
+        }
+        return None;
+    }
+    Some(format!(
+        "{}\n--- expected\n{}\n+++ actual\n{}",
+        path.display(),
+        expected,
+        actual
+    ))
+}
+
+#[test]
+fn corpus() {
+    let update = env::var("UPDATE_EXPECT").is_ok_and(|v| v == "1");
+    let dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/corpus");
+    let mut paths = fs::read_dir(&dir)
+        .unwrap()
+        .map(|entry| entry.unwrap().path())
+        .filter(|path| path.extension().is_some_and(|ext| ext == "lust"))
+        .collect::<Vec<_>>();
+    paths.sort();
+
+    let mut failures = vec![];
+    for path in paths {
+        let src = fs::read_to_string(&path).unwrap();
+        let (out, err) = run(&src);
+        failures.extend(check(&path.with_extension("out"), &out, update));
+        failures.extend(check(&path.with_extension("err"), &err, update));
+    }
+    if !failures.is_empty() {
+        panic!(
+            "{} corpus mismatch(es), rerun with UPDATE_EXPECT=1 to rebaseline\n\n{}",
+            failures.len(),
+            failures.join("\n")
+        );
+    }
+}
@@ -0,0 +1,231 @@
+//! Conformance of the reader against the R7RS external representations
+//! (section 7.1.2). Tests for syntax the reader doesn't accept yet are
+//! marked `#[ignore]` with the reason, so `cargo test -- --ignored` lists
+//! what is still missing.
+use lust_syntax::read::{
+    error::SyntaxError,
+    read,
+    sexpr::{AtomKind, Lit, Sexpr, SexprKind},
+};
+
+fn datum(src: &str) -> Sexpr {
+    match read(src) {
+        (Some(root), errs) if errs.is_empty() => {
+            assert_eq!(root.sexprs.len(), 1, "expected one datum in {:?}", src);
+            root.sexprs[0].clone()
+        }
+        (_, errs) => panic!("failed to read {:?}: {:?}", src, errs),
+    }
+}
+
+fn atom(src: &str) -> AtomKind {
+    match datum(src).into_kind() {
+        SexprKind::Atom(atom) => *atom.kind,
+        kind => panic!("expected atom for {:?}, got {:?}", src, kind),
+    }
+}
+
+fn lit(src: &str) -> Lit {
+    match atom(src) {
+        AtomKind::Lit(lit) => lit,
+        kind => panic!("expected literal for {:?}, got {:?}", src, kind),
+    }
+}
+
+fn sym(src: &str) -> String {
+    match atom(src) {
+        AtomKind::Sym(name) => name.to_string(),
+        kind => panic!("expected symbol for {:?}, got {:?}", src, kind),
+    }
+}
+
+fn prints(src: &str, expected: &str) {
+    assert_eq!(datum(src).to_string(), expected);
+}
+
+// numbers
+
+#[test]
+fn decimal_integer() {
+    assert!(matches!(lit("42"), Lit::Int(_)));
+    assert!(matches!(lit("0"), Lit::Int(_)));
+}
+
+#[test]
+fn signed_integer() {
+    assert_eq!(lit("-42").to_string(), "-42");
+    assert_eq!(lit("+7").to_string(), "7");
+}
+
+#[test]
+fn radix_prefixed_integer() {
+    assert_eq!(lit("#xff").to_string(), "255");
+    assert_eq!(lit("#o17").to_string(), "15");
+    assert_eq!(lit("#b101").to_string(), "5");
+}
+
+#[test]
+fn rational() {
+    assert!(matches!(lit("1/2"), Lit::Rational(_)));
+}
+
+#[test]
+fn decimal_real() {
+    assert!(matches!(lit("3.14"), Lit::Real(_)));
+    assert!(matches!(lit("1e10"), Lit::Real(_)));
+}
+
+#[test]
+fn special_reals() {
+    assert!(matches!(lit("+inf.0"), Lit::Real(_)));
+    assert!(matches!(lit("-inf.0"), Lit::Real(_)));
+    assert!(matches!(lit("+nan.0"), Lit::Real(_)));
+}
+
+#[test]
+fn complex() {
+    assert!(matches!(lit("1+2i"), Lit::Complex(_)));
+    assert!(matches!(lit("-i"), Lit::Complex(_)));
+    assert!(matches!(lit("1@0"), Lit::Complex(_)));
+}
+
+// booleans
+
+#[test]
+fn short_booleans() {
+    assert_eq!(lit("#t"), Lit::Bool(true));
+    assert_eq!(lit("#f"), Lit::Bool(false));
+}
+
+#[test]
+#[ignore = "not yet supported: #true/#false"]
+fn long_booleans() {
+    assert_eq!(lit("#true"), Lit::Bool(true));
+    assert_eq!(lit("#false"), Lit::Bool(false));
+}
+
+// characters
+
+#[test]
+fn characters() {
+    assert_eq!(lit(r"#\a"), Lit::Char('a'));
+    assert_eq!(lit(r"#\space"), Lit::Char(' '));
+    assert_eq!(lit(r"#\newline"), Lit::Char('\n'));
+    assert_eq!(lit(r"#\x41"), Lit::Char('A'));
+    assert_eq!(lit(r"#\λ"), Lit::Char('λ'));
+    prints(r"(#\a #\( #\space #\x7)", r"(#\a #\( #\space #\x7)");
+}
+
+// strings
+
+#[test]
+fn string() {
+    assert!(matches!(lit(r#""hello""#), Lit::String(_)));
+}
+
+#[test]
+fn string_escapes() {
+    assert_eq!(lit(r#""a\nb""#), Lit::String("a\nb".into()));
+    assert_eq!(lit(r#""\x41;""#), Lit::String("A".into()));
+}
+
+// symbols
+
+#[test]
+fn symbols() {
+    assert_eq!(sym("foo"), "foo");
+    assert_eq!(sym("+"), "+");
+    assert_eq!(sym("->x"), "->x");
+    assert_eq!(sym("set-car!"), "set-car!");
+    assert_eq!(sym("<=?"), "<=?");
+}
+
+#[test]
+fn pipe_symbols() {
+    assert_eq!(sym("|hello world|"), "hello world");
+}
+
+// lists
+
+#[test]
+fn proper_list() {
+    prints("(a b c)", "(a b c)");
+    prints("(a (b c) d)", "(a (b c) d)");
+}
+
+#[test]
+fn empty_list() {
+    prints("()", "()");
+}
+
+#[test]
+fn dotted_pair() {
+    prints("(a . b)", "(a . b)");
+    prints("(a b . c)", "(a b . c)");
+}
+
+#[test]
+fn quote_abbreviations() {
+    prints("'a", "(quote a)");
+    prints(
+        "`(a ,b ,@c)",
+        "(quasiquote (a (unquote b) (unquote-splicing c)))",
+    );
+}
+
+// vectors and bytevectors
+
+#[test]
+#[ignore = "not supported: lust vectors are written #[...]"]
+fn vector() {
+    datum("#(1 2 3)");
+}
+
+#[test]
+fn bytevector() {
+    datum("#u8(0 255 16)");
+}
+
+// datum labels
+
+#[test]
+fn datum_labels() {
+    prints("#0=(a b)", "#0=(a b)");
+    prints("(#0=(a) #0#)", "(#0=(a) #0#)");
+    prints("#0=(a b . #0#)", "#0=(a b . #0#)");
+}
+
+#[test]
+fn datum_label_errors() {
+    assert!(matches!(
+        read("(#0# #0=a)").1.as_slice(),
+        [SyntaxError::UndefinedLabel(0, _)]
+    ));
+    assert!(matches!(
+        read("(#1=a #1=b)").1.as_slice(),
+        [SyntaxError::DuplicateLabel(1, _)]
+    ));
+    // each top level datum has its own labels
+    assert!(matches!(
+        read("#0=a #0#").1.as_slice(),
+        [SyntaxError::UndefinedLabel(0, _)]
+    ));
+    assert!(read("#0=a #0=b").1.is_empty());
+}
+
+// comments
+
+#[test]
+fn line_comment() {
+    prints("; comment\n(a b)", "(a b)");
+}
+
+#[test]
+fn block_comment() {
+    prints("#| outer #| inner |# |# (a b)", "(a b)");
+}
+
+#[test]
+fn datum_comment() {
+    prints("(a #;(ignored) b)", "(a b)");
+}
@@ -0,0 +1,76 @@
+use lust_syntax::read::{
+    read,
+    sexpr::{AtomKind, Lit, Root, Sexpr, SexprKind},
+};
+use lust_utils::intern::InternedString;
+use proptest::prelude::*;
+
+fn atom() -> impl Strategy<Value = String> {
+    prop_oneof![
+        any::<u32>().prop_map(|n| n.to_string()),
+        any::<bool>().prop_map(|b| if b { "#t" } else { "#f" }.to_string()),
+        "[a-z][a-z0-9_?!-]{0,8}",
+        // names that only read back as themselves between pipes
+        "[a-z:#+|. -]{0,4}".prop_map(|s| AtomKind::Sym(InternedString::from(s)).to_string()),
+        "[a-z ]{0,8}".prop_map(|s| format!("\"{}\"", s)),
+        any::<char>().prop_map(|c| Lit::Char(c).to_string()),
+        prop::collection::vec(any::<u8>(), 0..4).prop_map(|b| Lit::Bytes(b).to_string()),
+    ]
+}
+
+fn sexpr() -> impl Strategy<Value = String> {
+    atom().prop_recursive(8, 64, 8, |inner| {
+        prop_oneof![
+            prop::collection::vec(inner.clone(), 0..8).prop_map(|v| format!("({})", v.join(" "))),
+            prop::collection::vec(inner.clone(), 0..8).prop_map(|v| format!("#[{}]", v.join(" "))),
+            prop::collection::vec(inner.clone(), 1..8).prop_map(|v| format!("[{}]", v.join(" "))),
+            inner.clone().prop_map(|s| format!("'{}", s)),
+            inner.clone().prop_map(|s| format!("`{}", s)),
+            inner.prop_map(|s| format!(",{}", s)),
+        ]
+    })
+}
+
+fn read_clean(src: &str) -> Root {
+    match read(src) {
+        (Some(root), errs) if errs.is_empty() => root,
+        (_, errs) => panic!("failed to read {:?}: {:?}", src, errs),
+    }
+}
+
+// The data without spans, which differ between the source and the printed
+// text, with the kind of every atom, so a #t that came back as the symbol
+// true doesn't pass.
+fn shape(sexpr: &Sexpr) -> String {
+    match sexpr.kind.as_ref() {
+        SexprKind::Atom(atom) => format!("{:?}", atom.kind),
+        SexprKind::List(list) => {
+            format!("({})", list.iter().map(shape).collect::<Vec<_>>().join(" "))
+        }
+        SexprKind::DottedList(list, tail) => format!(
+            "({} . {})",
+            list.iter().map(shape).collect::<Vec<_>>().join(" "),
+            shape(tail)
+        ),
+        SexprKind::DatumLabel(n, sexpr) => format!("#{}={}", n, shape(sexpr)),
+        SexprKind::DatumRef(n) => format!("#{}#", n),
+    }
+}
+
+fn shapes(root: &Root) -> Vec<String> {
+    root.sexprs.iter().map(shape).collect()
+}
+
+proptest! {
+    #[test]
+    fn print_read_roundtrip(src in sexpr()) {
+        let root = read_clean(&src);
+        prop_assert_eq!(shapes(&read_clean(&root.to_string())), shapes(&root));
+    }
+
+    #[test]
+    fn many_roots_roundtrip(srcs in prop::collection::vec(sexpr(), 0..8)) {
+        let root = read_clean(&srcs.join("\n"));
+        prop_assert_eq!(shapes(&read_clean(&root.to_string())), shapes(&root));
+    }
+}
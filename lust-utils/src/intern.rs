@@ -2,56 +2,109 @@ use lasso::{Spur, ThreadedRodeo};
 use once_cell::sync::Lazy;
 use std::{
     borrow::Borrow,
+    cmp::Ordering,
     fmt::{Debug, Display},
     ops::Deref,
 };
 
-pub static mut INTERNER: Lazy<ThreadedRodeo> = Lazy::new(|| ThreadedRodeo::default());
+pub static INTERNER: Lazy<ThreadedRodeo> = Lazy::new(ThreadedRodeo::default);
 
-#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Default)]
-pub struct InternedString {
-    key: Spur,
+// Strings up to this many bytes are stored inline instead of in the
+// interner, so most symbols never take the interner's lock.
+const INLINE_CAP: usize = 15;
+
+// A string is always inline if it fits, so equal strings always have the
+// same representation and the derived comparisons are consistent.
+// Ordering is by name rather than by representation, so maps keyed by
+// names iterate the same way whatever order they were interned in.
+#[derive(Clone, Copy, PartialEq, Eq, Hash)]
+pub struct InternedString(Repr);
+
+#[derive(Clone, Copy, PartialEq, Eq, Hash)]
+enum Repr {
+    Inline { len: u8, bytes: [u8; INLINE_CAP] },
+    Interned(Spur),
+}
+
+impl InternedString {
+    fn inline(name: &str) -> Option<Self> {
+        if name.len() > INLINE_CAP {
+            return None;
+        }
+        let mut bytes = [0; INLINE_CAP];
+        bytes[..name.len()].copy_from_slice(name.as_bytes());
+        Some(Self(Repr::Inline {
+            len: name.len() as u8,
+            bytes,
+        }))
+    }
+
+    fn as_str(&self) -> &str {
+        match &self.0 {
+            // only ever built from a whole &str
+            Repr::Inline { len, bytes } => unsafe {
+                std::str::from_utf8_unchecked(&bytes[..*len as usize])
+            },
+            Repr::Interned(key) => INTERNER.resolve(key),
+        }
+    }
+}
+
+impl Default for InternedString {
+    fn default() -> Self {
+        Self(Repr::Inline {
+            len: 0,
+            bytes: [0; INLINE_CAP],
+        })
+    }
 }
 
 impl From<Spur> for InternedString {
     fn from(key: Spur) -> Self {
-        Self { key }
+        let name = INTERNER.resolve(&key);
+        Self::inline(name).unwrap_or(Self(Repr::Interned(key)))
     }
 }
 
 impl From<&str> for InternedString {
     fn from(name: &str) -> Self {
-        Self {
-            key: unsafe { INTERNER.get_or_intern(name) },
-        }
+        Self::inline(name).unwrap_or_else(|| Self(Repr::Interned(INTERNER.get_or_intern(name))))
     }
 }
 
 impl From<String> for InternedString {
     fn from(name: String) -> Self {
-        Self {
-            key: unsafe { INTERNER.get_or_intern(name) },
-        }
+        Self::inline(&name).unwrap_or_else(|| Self(Repr::Interned(INTERNER.get_or_intern(name))))
     }
 }
 
 impl Debug for InternedString {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        write!(f, "InternedString({})", unsafe {
-            INTERNER.resolve(&self.key)
-        })
+        write!(f, "InternedString({})", self.as_str())
     }
 }
 
 impl Display for InternedString {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        write!(f, "{}", unsafe { INTERNER.resolve(&self.key) })
+        write!(f, "{}", self.as_str())
+    }
+}
+
+impl PartialOrd for InternedString {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for InternedString {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.as_str().cmp(other.as_str())
     }
 }
 
 impl Borrow<str> for InternedString {
     fn borrow(&self) -> &str {
-        unsafe { INTERNER.resolve(&self.key) }
+        self.as_str()
     }
 }
 
@@ -59,6 +112,36 @@ impl Deref for InternedString {
     type Target = str;
 
     fn deref(&self) -> &Self::Target {
-        unsafe { INTERNER.resolve(&self.key) }
+        self.as_str()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{InternedString, Repr};
+
+    #[test]
+    fn inline() {
+        let short = InternedString::from("fifteen-bytes!!");
+        let long = InternedString::from("sixteen-bytes!!!");
+        assert!(matches!(short.0, Repr::Inline { .. }));
+        assert!(matches!(long.0, Repr::Interned(_)));
+        assert_eq!(&*short, "fifteen-bytes!!");
+        assert_eq!(&*long, "sixteen-bytes!!!");
+        assert_eq!(short, InternedString::from(String::from("fifteen-bytes!!")));
+        assert_eq!(long, InternedString::from(String::from("sixteen-bytes!!!")));
+        assert_eq!(&*InternedString::default(), "");
+    }
+
+    #[test]
+    fn ordered_by_name() {
+        let mut names = ["zeta-is-a-long-name", "b", "alpha-is-a-long-name", "a"]
+            .map(InternedString::from)
+            .to_vec();
+        names.sort();
+        assert_eq!(
+            names.iter().map(|name| &**name).collect::<Vec<_>>(),
+            ["a", "alpha-is-a-long-name", "b", "zeta-is-a-long-name"]
+        );
     }
 }
@@ -1,5 +1,7 @@
 pub mod intern;
 pub mod list;
+pub mod map;
 pub mod num;
 pub mod span;
 pub mod unique_id;
+pub mod vector;
@@ -1,15 +1,56 @@
-use std::fmt::Display;
-
-#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Default)]
+use std::{
+    cmp::Ordering,
+    fmt::{Debug, Display},
+    ops::{Deref, DerefMut},
+};
+
+// Clone, comparison and drop all walk the list in a loop rather than
+// recursing down the tail, so a long list can't overflow the stack.
+#[derive(Default)]
 pub enum List<T> {
     #[default]
     Empty,
     Pair {
         head: T,
-        tail: Box<Self>,
+        tail: Tail<T>,
     },
 }
 
+// The rest of a list, boxed. Dropping it drops the cells after it one at a
+// time.
+pub struct Tail<T>(Box<List<T>>);
+
+impl<T> Tail<T> {
+    pub fn new(list: List<T>) -> Self {
+        Self(Box::new(list))
+    }
+}
+
+impl<T> Deref for Tail<T> {
+    type Target = List<T>;
+
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+
+impl<T> DerefMut for Tail<T> {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        &mut self.0
+    }
+}
+
+impl<T> Drop for Tail<T> {
+    fn drop(&mut self) {
+        let mut list = std::mem::take(&mut *self.0);
+        while let List::Pair { tail, .. } = &mut list {
+            let next = std::mem::take(&mut *tail.0);
+            // the old cell's tail is now empty, so this doesn't recurse
+            list = next;
+        }
+    }
+}
+
 impl<T> List<T> {
     pub fn head(&self) -> Option<&T> {
         match self {
@@ -29,10 +70,20 @@ impl<T> List<T> {
         let tail = std::mem::replace(self, Self::Empty);
         *self = Self::Pair {
             head,
-            tail: Box::new(tail),
+            tail: Tail::new(tail),
         };
     }
 
+    pub fn pop_front(&mut self) -> Option<T> {
+        match std::mem::take(self) {
+            Self::Empty => None,
+            Self::Pair { head, mut tail } => {
+                *self = std::mem::take(&mut *tail);
+                Some(head)
+            }
+        }
+    }
+
     pub fn push_back(&mut self, head: T) {
         let mut tail = self;
         loop {
@@ -40,7 +91,7 @@ impl<T> List<T> {
                 Self::Empty => {
                     *tail = Self::Pair {
                         head,
-                        tail: Box::new(Self::Empty),
+                        tail: Tail::new(Self::Empty),
                     };
                     break;
                 }
@@ -56,7 +107,40 @@ impl<T> List<T> {
     }
 }
 
-impl<'a, T> Display for List<T>
+impl<T: Clone> Clone for List<T> {
+    fn clone(&self) -> Self {
+        List::from(self.iter().cloned().collect::<Vec<_>>())
+    }
+}
+
+impl<T: PartialEq> PartialEq for List<T> {
+    fn eq(&self, other: &Self) -> bool {
+        self.iter().eq(other.iter())
+    }
+}
+
+impl<T: Eq> Eq for List<T> {}
+
+// Lexicographic, with a list before any longer list it starts.
+impl<T: PartialOrd> PartialOrd for List<T> {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        self.iter().partial_cmp(other.iter())
+    }
+}
+
+impl<T: Ord> Ord for List<T> {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.iter().cmp(other.iter())
+    }
+}
+
+impl<T: Debug> Debug for List<T> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_list().entries(self.iter()).finish()
+    }
+}
+
+impl<T> Display for List<T>
 where
     T: Display,
 {
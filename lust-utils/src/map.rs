@@ -0,0 +1,464 @@
+/*
+ * A persistent hash map, implemented as a hash array mapped trie. Each
+ * level of the trie consumes 5 bits of the key's hash, so lookups, inserts
+ * and removals touch O(log32 n) nodes, and an update copies only the path
+ * to the changed entry. Everything else is shared with the original map.
+ * Keys whose whole hashes are equal share a collision node.
+ */
+use std::{
+    borrow::Borrow,
+    collections::hash_map::DefaultHasher,
+    fmt::Debug,
+    hash::{Hash, Hasher},
+    rc::Rc,
+    slice,
+};
+
+const BITS: u32 = 5;
+const MASK: u64 = (1 << BITS) - 1;
+
+pub struct Map<K, V> {
+    root: Option<Rc<Node<K, V>>>,
+    len: usize,
+}
+
+#[derive(Debug, Clone)]
+enum Node<K, V> {
+    // children are ordered by their bit in the bitmap
+    Branch {
+        bitmap: u32,
+        children: Vec<Child<K, V>>,
+    },
+    Collision {
+        hash: u64,
+        entries: Vec<(K, V)>,
+    },
+}
+
+#[derive(Debug, Clone)]
+enum Child<K, V> {
+    Leaf(u64, K, V),
+    Node(Rc<Node<K, V>>),
+}
+
+enum Removed<K, V> {
+    NotFound,
+    // what replaces the node in its parent, if anything
+    Removed(Option<Child<K, V>>),
+}
+
+impl<K, V> Map<K, V> {
+    pub fn new() -> Self {
+        Self { root: None, len: 0 }
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    pub fn iter(&self) -> Iter<'_, K, V> {
+        Iter {
+            stack: self.root.iter().map(|root| NodeIter::new(root)).collect(),
+        }
+    }
+}
+
+impl<K: Hash + Eq + Clone, V: Clone> Map<K, V> {
+    pub fn get<Q>(&self, key: &Q) -> Option<&V>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        let hash = hash(key);
+        let mut node = self.root.as_deref()?;
+        let mut shift = 0;
+        loop {
+            match node {
+                Node::Branch { bitmap, children } => {
+                    let bit = bit(hash, shift);
+                    if bitmap & bit == 0 {
+                        return None;
+                    }
+                    match &children[index(*bitmap, bit)] {
+                        Child::Leaf(h, k, v) => {
+                            return (*h == hash && k.borrow() == key).then_some(v)
+                        }
+                        Child::Node(next) => {
+                            node = next;
+                            shift += BITS;
+                        }
+                    }
+                }
+                Node::Collision { entries, .. } => {
+                    return entries
+                        .iter()
+                        .find(|(k, _)| k.borrow() == key)
+                        .map(|(_, v)| v)
+                }
+            }
+        }
+    }
+
+    pub fn contains_key<Q>(&self, key: &Q) -> bool
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        self.get(key).is_some()
+    }
+
+    // Returns a new map with `key` set to `value`, leaving this one as is.
+    pub fn insert(&self, key: K, value: V) -> Self {
+        let hash = hash(&key);
+        let (root, added) = match &self.root {
+            Some(root) => insert(root, 0, hash, key, value),
+            None => (
+                Node::Branch {
+                    bitmap: bit(hash, 0),
+                    children: vec![Child::Leaf(hash, key, value)],
+                },
+                true,
+            ),
+        };
+        Self {
+            root: Some(Rc::new(root)),
+            len: self.len + added as usize,
+        }
+    }
+
+    // Returns a new map without `key`, leaving this one as is.
+    pub fn remove<Q>(&self, key: &Q) -> Self
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        let Some(root) = &self.root else {
+            return self.clone();
+        };
+        match remove(root, 0, hash(key), key) {
+            Removed::NotFound => self.clone(),
+            Removed::Removed(None) => Self::new(),
+            Removed::Removed(Some(Child::Node(root))) => Self {
+                root: Some(root),
+                len: self.len - 1,
+            },
+            Removed::Removed(Some(Child::Leaf(..))) => unreachable!("the root is never collapsed"),
+        }
+    }
+}
+
+fn hash<Q: Hash + ?Sized>(key: &Q) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    key.hash(&mut hasher);
+    hasher.finish()
+}
+
+fn bit(hash: u64, shift: u32) -> u32 {
+    1 << ((hash >> shift) & MASK)
+}
+
+// the position of the child for `bit` among the children present
+fn index(bitmap: u32, bit: u32) -> usize {
+    (bitmap & (bit - 1)).count_ones() as usize
+}
+
+fn insert<K: Hash + Eq + Clone, V: Clone>(
+    node: &Node<K, V>,
+    shift: u32,
+    hash: u64,
+    key: K,
+    value: V,
+) -> (Node<K, V>, bool) {
+    match node {
+        Node::Branch { bitmap, children } => {
+            let bit = bit(hash, shift);
+            let i = index(*bitmap, bit);
+            let mut children = children.clone();
+            if bitmap & bit == 0 {
+                children.insert(i, Child::Leaf(hash, key, value));
+                return (
+                    Node::Branch {
+                        bitmap: bitmap | bit,
+                        children,
+                    },
+                    true,
+                );
+            }
+            let (child, added) = match &children[i] {
+                Child::Leaf(h, k, _) if *h == hash && *k == key => {
+                    (Child::Leaf(hash, key, value), false)
+                }
+                Child::Leaf(h, k, v) => (
+                    Child::Node(Rc::new(merge(
+                        shift + BITS,
+                        (*h, k.clone(), v.clone()),
+                        (hash, key, value),
+                    ))),
+                    true,
+                ),
+                Child::Node(next) => {
+                    let (next, added) = insert(next, shift + BITS, hash, key, value);
+                    (Child::Node(Rc::new(next)), added)
+                }
+            };
+            children[i] = child;
+            (
+                Node::Branch {
+                    bitmap: *bitmap,
+                    children,
+                },
+                added,
+            )
+        }
+        Node::Collision {
+            hash: collision,
+            entries,
+        } if *collision == hash => {
+            let mut entries = entries.clone();
+            match entries.iter().position(|(k, _)| *k == key) {
+                Some(i) => {
+                    entries[i] = (key, value);
+                    (Node::Collision { hash, entries }, false)
+                }
+                None => {
+                    entries.push((key, value));
+                    (Node::Collision { hash, entries }, true)
+                }
+            }
+        }
+        // a different hash ends up here when the collision node sits
+        // higher than the bits the two hashes differ in, so push it down
+        // a level
+        Node::Collision {
+            hash: collision, ..
+        } => {
+            let branch = Node::Branch {
+                bitmap: bit(*collision, shift),
+                children: vec![Child::Node(Rc::new(node.clone()))],
+            };
+            insert(&branch, shift, hash, key, value)
+        }
+    }
+}
+
+// a node holding two leaves that share the bits of their hashes below
+// `shift`
+fn merge<K, V>(shift: u32, a: (u64, K, V), b: (u64, K, V)) -> Node<K, V> {
+    if a.0 == b.0 {
+        return Node::Collision {
+            hash: a.0,
+            entries: vec![(a.1, a.2), (b.1, b.2)],
+        };
+    }
+    let (bit_a, bit_b) = (bit(a.0, shift), bit(b.0, shift));
+    if bit_a == bit_b {
+        return Node::Branch {
+            bitmap: bit_a,
+            children: vec![Child::Node(Rc::new(merge(shift + BITS, a, b)))],
+        };
+    }
+    let (first, second) = if bit_a < bit_b { (a, b) } else { (b, a) };
+    Node::Branch {
+        bitmap: bit_a | bit_b,
+        children: vec![
+            Child::Leaf(first.0, first.1, first.2),
+            Child::Leaf(second.0, second.1, second.2),
+        ],
+    }
+}
+
+fn remove<K, V, Q>(node: &Node<K, V>, shift: u32, hash: u64, key: &Q) -> Removed<K, V>
+where
+    K: Borrow<Q> + Clone,
+    V: Clone,
+    Q: Eq + ?Sized,
+{
+    match node {
+        Node::Branch { bitmap, children } => {
+            let bit = bit(hash, shift);
+            if bitmap & bit == 0 {
+                return Removed::NotFound;
+            }
+            let i = index(*bitmap, bit);
+            let replacement = match &children[i] {
+                Child::Leaf(h, k, _) if *h == hash && k.borrow() == key => None,
+                Child::Leaf(..) => return Removed::NotFound,
+                Child::Node(next) => match remove(next, shift + BITS, hash, key) {
+                    Removed::NotFound => return Removed::NotFound,
+                    Removed::Removed(child) => child,
+                },
+            };
+            let mut children = children.clone();
+            let bitmap = match replacement {
+                Some(child) => {
+                    children[i] = child;
+                    *bitmap
+                }
+                None => {
+                    children.remove(i);
+                    bitmap & !bit
+                }
+            };
+            if children.is_empty() {
+                return Removed::Removed(None);
+            }
+            // a lone leaf moves up into the parent, except at the root
+            if shift > 0 && children.len() == 1 && matches!(children[0], Child::Leaf(..)) {
+                return Removed::Removed(children.pop());
+            }
+            Removed::Removed(Some(Child::Node(Rc::new(Node::Branch {
+                bitmap,
+                children,
+            }))))
+        }
+        Node::Collision { hash: h, entries } => {
+            let Some(i) = entries.iter().position(|(k, _)| k.borrow() == key) else {
+                return Removed::NotFound;
+            };
+            let mut entries = entries.clone();
+            entries.remove(i);
+            if entries.len() == 1 {
+                let (k, v) = entries.pop().unwrap();
+                Removed::Removed(Some(Child::Leaf(*h, k, v)))
+            } else {
+                Removed::Removed(Some(Child::Node(Rc::new(Node::Collision {
+                    hash: *h,
+                    entries,
+                }))))
+            }
+        }
+    }
+}
+
+impl<K, V> Clone for Map<K, V> {
+    fn clone(&self) -> Self {
+        Self {
+            root: self.root.clone(),
+            len: self.len,
+        }
+    }
+}
+
+impl<K, V> Default for Map<K, V> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<K: Debug, V: Debug> Debug for Map<K, V> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_map().entries(self.iter()).finish()
+    }
+}
+
+impl<K: Hash + Eq + Clone, V: Clone + PartialEq> PartialEq for Map<K, V> {
+    fn eq(&self, other: &Self) -> bool {
+        self.len == other.len && self.iter().all(|(k, v)| other.get(k) == Some(v))
+    }
+}
+
+impl<K: Hash + Eq + Clone, V: Clone> FromIterator<(K, V)> for Map<K, V> {
+    fn from_iter<I: IntoIterator<Item = (K, V)>>(iter: I) -> Self {
+        iter.into_iter()
+            .fold(Self::new(), |map, (k, v)| map.insert(k, v))
+    }
+}
+
+pub struct Iter<'a, K, V> {
+    stack: Vec<NodeIter<'a, K, V>>,
+}
+
+enum NodeIter<'a, K, V> {
+    Branch(slice::Iter<'a, Child<K, V>>),
+    Collision(slice::Iter<'a, (K, V)>),
+}
+
+impl<'a, K, V> NodeIter<'a, K, V> {
+    fn new(node: &'a Node<K, V>) -> Self {
+        match node {
+            Node::Branch { children, .. } => Self::Branch(children.iter()),
+            Node::Collision { entries, .. } => Self::Collision(entries.iter()),
+        }
+    }
+}
+
+impl<'a, K, V> Iterator for Iter<'a, K, V> {
+    type Item = (&'a K, &'a V);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            match self.stack.last_mut()? {
+                NodeIter::Branch(children) => match children.next() {
+                    Some(Child::Leaf(_, k, v)) => return Some((k, v)),
+                    Some(Child::Node(node)) => self.stack.push(NodeIter::new(node)),
+                    None => {
+                        self.stack.pop();
+                    }
+                },
+                NodeIter::Collision(entries) => match entries.next() {
+                    Some((k, v)) => return Some((k, v)),
+                    None => {
+                        self.stack.pop();
+                    }
+                },
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Map;
+    use std::hash::{Hash, Hasher};
+
+    #[test]
+    fn persistent() {
+        let a = Map::new().insert("x", 1).insert("y", 2);
+        let b = a.insert("x", 3).remove("y");
+        assert_eq!((a.get("x"), a.get("y"), a.len()), (Some(&1), Some(&2), 2));
+        assert_eq!((b.get("x"), b.get("y"), b.len()), (Some(&3), None, 1));
+    }
+
+    #[test]
+    fn many() {
+        let map = (0..10_000).map(|i| (i, i * 2)).collect::<Map<_, _>>();
+        assert_eq!(map.len(), 10_000);
+        assert!((0..10_000).all(|i| map.get(&i) == Some(&(i * 2))));
+        assert_eq!(map.iter().count(), 10_000);
+        let evens = (0..10_000)
+            .filter(|i| i % 2 == 1)
+            .fold(map.clone(), |map, i| map.remove(&i));
+        assert_eq!(evens.len(), 5_000);
+        assert!((0..10_000).all(|i| evens.contains_key(&i) == (i % 2 == 0)));
+        let empty = (0..10_000).fold(map, |map, i| map.remove(&i));
+        assert!(empty.is_empty());
+        assert_eq!(empty, Map::new());
+    }
+
+    #[derive(Debug, Clone, PartialEq, Eq)]
+    struct Collide(u32);
+
+    impl Hash for Collide {
+        fn hash<H: Hasher>(&self, state: &mut H) {
+            0.hash(state)
+        }
+    }
+
+    #[test]
+    fn collisions() {
+        let map = (0..4).map(|i| (Collide(i), i)).collect::<Map<_, _>>();
+        assert_eq!(map.len(), 4);
+        assert!((0..4).all(|i| map.get(&Collide(i)) == Some(&i)));
+        let map = map
+            .remove(&Collide(1))
+            .remove(&Collide(2))
+            .remove(&Collide(0));
+        assert_eq!(map.len(), 1);
+        assert_eq!(map.get(&Collide(3)), Some(&3));
+        assert!(map.remove(&Collide(3)).is_empty());
+    }
+}
@@ -1,16 +1,55 @@
+#[cfg(feature = "bignum")]
 use num_bigint::BigInt as NumBigInt;
-use num_rational::{BigRational as NumBigRational, Rational64};
+use num_complex::Complex64;
+#[cfg(feature = "bignum")]
+use num_rational::BigRational as NumBigRational;
+use num_rational::Rational64;
 use std::{fmt::Display, str::FromStr};
 
 #[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
 pub struct Int(i64);
 
+impl Int {
+    pub fn new(n: i64) -> Self {
+        Self(n)
+    }
+
+    pub fn value(&self) -> i64 {
+        self.0
+    }
+}
+
 impl Display for Int {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         write!(f, "{}", self.0)
     }
 }
 
+impl Int {
+    pub fn from_str_radix(s: &str, radix: u32) -> Result<Self, std::num::ParseIntError> {
+        Ok(Self(i64::from_str_radix(s, radix)?))
+    }
+
+    // Digits past 9 are lower case letters. Panics if the radix isn't in
+    // 2..=36, like from_str_radix.
+    pub fn to_string_radix(&self, radix: u32) -> String {
+        assert!((2..=36).contains(&radix), "radix {} is out of range", radix);
+        let mut n = self.0.unsigned_abs();
+        let mut digits = vec![];
+        loop {
+            digits.push(std::char::from_digit((n % radix as u64) as u32, radix).unwrap());
+            n /= radix as u64;
+            if n == 0 {
+                break;
+            }
+        }
+        if self.0 < 0 {
+            digits.push('-');
+        }
+        digits.iter().rev().collect()
+    }
+}
+
 impl FromStr for Int {
     type Err = std::num::ParseIntError;
 
@@ -19,15 +58,25 @@ impl FromStr for Int {
     }
 }
 
+#[cfg(feature = "bignum")]
 #[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
 pub struct BigInt(NumBigInt);
 
+#[cfg(feature = "bignum")]
 impl Display for BigInt {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         write!(f, "{}", self.0)
     }
 }
 
+#[cfg(feature = "bignum")]
+impl BigInt {
+    pub fn from_str_radix(s: &str, radix: u32) -> Option<Self> {
+        NumBigInt::parse_bytes(s.as_bytes(), radix).map(Self)
+    }
+}
+
+#[cfg(feature = "bignum")]
 impl FromStr for BigInt {
     type Err = num_bigint::ParseBigIntError;
 
@@ -39,9 +88,52 @@ impl FromStr for BigInt {
 #[derive(Debug, Clone, PartialEq, PartialOrd)]
 pub struct Real(f64);
 
+// How a real is written out. Infinities and NaN are always written the
+// r7rs way, +inf.0, -inf.0 and +nan.0.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RealFormat {
+    // the fewest digits that read back as the same number, so 0.1 is 0.1,
+    // with an exponent for very large and very small numbers
+    Shortest,
+    // this many digits after the point
+    Fixed(usize),
+    // d.ddde-n, with this many digits after the point or else the fewest
+    // that read back
+    Exponent(Option<usize>),
+}
+
+impl Real {
+    pub fn format(&self, format: RealFormat) -> String {
+        let x = self.0;
+        if x.is_nan() {
+            return "+nan.0".to_string();
+        } else if x.is_infinite() {
+            return format!("{}inf.0", if x > 0.0 { "+" } else { "-" });
+        }
+        match format {
+            RealFormat::Shortest => {
+                let s = if x != 0.0 && (x.abs() >= 1e21 || x.abs() < 1e-7) {
+                    format!("{:e}", x)
+                } else {
+                    format!("{}", x)
+                };
+                // 1.0 is written 1.0, so it reads back as a real
+                if s.contains(['.', 'e']) {
+                    s
+                } else {
+                    s + ".0"
+                }
+            }
+            RealFormat::Fixed(digits) => format!("{:.*}", digits, x),
+            RealFormat::Exponent(Some(digits)) => format!("{:.*e}", digits, x),
+            RealFormat::Exponent(None) => format!("{:e}", x),
+        }
+    }
+}
+
 impl Display for Real {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        write!(f, "{}", self.0)
+        write!(f, "{}", self.format(RealFormat::Shortest))
     }
 }
 
@@ -49,7 +141,12 @@ impl FromStr for Real {
     type Err = std::num::ParseFloatError;
 
     fn from_str(s: &str) -> Result<Self, Self::Err> {
-        Ok(Self(s.parse()?))
+        match s {
+            "+inf.0" => Ok(Self(f64::INFINITY)),
+            "-inf.0" => Ok(Self(f64::NEG_INFINITY)),
+            "+nan.0" | "-nan.0" => Ok(Self(f64::NAN)),
+            _ => Ok(Self(s.parse()?)),
+        }
     }
 }
 
@@ -88,15 +185,18 @@ impl FromStr for Rational {
     }
 }
 
+#[cfg(feature = "bignum")]
 #[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
 pub struct BigRational(NumBigRational);
 
+#[cfg(feature = "bignum")]
 impl Display for BigRational {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         write!(f, "{}", self.0)
     }
 }
 
+#[cfg(feature = "bignum")]
 impl FromStr for BigRational {
     type Err = num_rational::ParseRatioError;
 
@@ -104,3 +204,132 @@ impl FromStr for BigRational {
         Ok(Self(s.parse()?))
     }
 }
+
+// 3+4i, -2.5i or 1@0.5 in polar form, with real parts.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Complex(Complex64);
+
+impl Complex {
+    pub fn new(re: f64, im: f64) -> Self {
+        Self(Complex64::new(re, im))
+    }
+
+    // the angle is in radians
+    pub fn from_polar(magnitude: f64, angle: f64) -> Self {
+        Self(Complex64::from_polar(magnitude, angle))
+    }
+
+    pub fn re(&self) -> f64 {
+        self.0.re
+    }
+
+    pub fn im(&self) -> f64 {
+        self.0.im
+    }
+}
+
+// By real part then imaginary part, which isn't a numeric order, only one
+// so literals can be sorted.
+impl PartialOrd for Complex {
+    fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
+        (self.0.re, self.0.im).partial_cmp(&(other.0.re, other.0.im))
+    }
+}
+
+// Always written in rectangular form. The imaginary part always has a sign
+// so it reads back as a complex, and a zero real part is left out.
+impl Display for Complex {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let im = Real(self.0.im).to_string();
+        let sign = if im.starts_with(['+', '-']) { "" } else { "+" };
+        if self.0.re == 0.0 {
+            write!(f, "{}{}i", sign, im)
+        } else {
+            write!(f, "{}{}{}i", Real(self.0.re), sign, im)
+        }
+    }
+}
+
+impl FromStr for Complex {
+    type Err = std::num::ParseFloatError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if let Some((magnitude, angle)) = s.split_once('@') {
+            let (magnitude, angle) = (magnitude.parse::<Real>()?, angle.parse::<Real>()?);
+            return Ok(Self::from_polar(magnitude.0, angle.0));
+        }
+        // without an i it's just a real
+        let Some(s) = s.strip_suffix('i') else {
+            return Ok(Self::new(s.parse::<Real>()?.0, 0.0));
+        };
+        // the imaginary part starts at the last sign that isn't leading or
+        // in an exponent
+        let split = s
+            .char_indices()
+            .rev()
+            .find(|&(i, c)| i > 0 && (c == '+' || c == '-') && !s[..i].ends_with(['e', 'E']))
+            .map_or(0, |(i, _)| i);
+        let (re, im) = s.split_at(split);
+        let re = if re.is_empty() {
+            0.0
+        } else {
+            re.parse::<Real>()?.0
+        };
+        let im = match im {
+            "+" => 1.0,
+            "-" => -1.0,
+            _ => im.parse::<Real>()?.0,
+        };
+        Ok(Self::new(re, im))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Complex, Int, Real, RealFormat};
+
+    #[test]
+    fn int_radix() {
+        assert_eq!(Int::new(255).to_string_radix(16), "ff");
+        assert_eq!(Int::new(-5).to_string_radix(2), "-101");
+        assert_eq!(Int::new(0).to_string_radix(8), "0");
+        assert_eq!(Int::new(i64::MIN).to_string_radix(16), "-8000000000000000");
+    }
+
+    #[test]
+    fn real_format() {
+        let shortest = |x: f64| Real(x).to_string();
+        assert_eq!(shortest(0.1), "0.1");
+        assert_eq!(shortest(0.1 + 0.2), "0.30000000000000004");
+        assert_eq!(shortest(1.0), "1.0");
+        assert_eq!(shortest(-0.0), "-0.0");
+        assert_eq!(shortest(1e300), "1e300");
+        assert_eq!(shortest(2.5e-10), "2.5e-10");
+        assert_eq!(shortest(f64::NEG_INFINITY), "-inf.0");
+        assert_eq!(Real(1.23456).format(RealFormat::Fixed(2)), "1.23");
+        assert_eq!(Real(1234.5).format(RealFormat::Exponent(Some(2))), "1.23e3");
+        assert_eq!(Real(1234.5).format(RealFormat::Exponent(None)), "1.2345e3");
+        assert_eq!(Real(f64::NAN).format(RealFormat::Fixed(2)), "+nan.0");
+    }
+
+    #[test]
+    fn complex() {
+        let read = |s: &str| s.parse::<Complex>().unwrap();
+        assert_eq!(read("3+4i"), Complex::new(3.0, 4.0));
+        assert_eq!(read("-2.5i"), Complex::new(0.0, -2.5));
+        assert_eq!(read("1e2-i"), Complex::new(100.0, -1.0));
+        assert_eq!(read("1.5e-3+2e+2i"), Complex::new(0.0015, 200.0));
+        assert_eq!(read("+inf.0-nan.0i").re(), f64::INFINITY);
+        assert_eq!(read("2@0"), Complex::new(2.0, 0.0));
+        assert!((read("1@3.141592653589793").re() + 1.0).abs() < 1e-12);
+        assert_eq!(read("3"), Complex::new(3.0, 0.0));
+        assert!("3+4".parse::<Complex>().is_err());
+        assert!("3+4j".parse::<Complex>().is_err());
+        assert_eq!(Complex::new(3.0, 4.0).to_string(), "3.0+4.0i");
+        assert_eq!(Complex::new(0.0, -2.5).to_string(), "-2.5i");
+        assert_eq!(Complex::new(1.0, f64::INFINITY).to_string(), "1.0+inf.0i");
+        for c in ["3.0+4.0i", "-2.5i", "1e300-1e-10i", "+inf.0+1.0i"] {
+            assert_eq!(read(c).to_string(), c);
+        }
+    }
+}
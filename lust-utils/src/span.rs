@@ -7,11 +7,16 @@ use std::{
 pub struct Span {
     start: u32,
     end: u32,
+    file: FileId,
 }
 
 impl Span {
     pub fn new(start: u32, end: u32) -> Self {
-        Self { start, end }
+        Self {
+            start,
+            end,
+            file: FileId::default(),
+        }
     }
 
     pub fn start(&self) -> u32 {
@@ -22,14 +27,38 @@ impl Span {
         self.end
     }
 
+    pub fn file(&self) -> FileId {
+        self.file
+    }
+
+    // the same offsets in another source
+    pub fn in_file(&self, file: FileId) -> Self {
+        Self { file, ..*self }
+    }
+
     pub fn extend(&self, other: Span) -> Self {
         Self {
             start: self.start.min(other.start),
             end: self.end.max(other.end),
+            file: self.file,
+        }
+    }
+
+    // the same span in a source that starts `offset` bytes earlier
+    pub fn shift(&self, offset: u32) -> Self {
+        Self {
+            start: self.start + offset,
+            end: self.end + offset,
+            file: self.file,
         }
     }
 }
 
+// Which source a span is in. Ids are handed out by a SourceCache, spans
+// made anywhere else are in the default one.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Default, Hash)]
+pub struct FileId(u32);
+
 impl Display for Span {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         write!(f, "{}..{}", self.start, self.end)
@@ -50,19 +79,13 @@ impl From<Span> for Range<usize> {
 
 impl From<Range<usize>> for Span {
     fn from(range: Range<usize>) -> Self {
-        Self {
-            start: range.start as u32,
-            end: range.end as u32,
-        }
+        Self::new(range.start as u32, range.end as u32)
     }
 }
 
 impl From<Range<u32>> for Span {
     fn from(range: Range<u32>) -> Self {
-        Self {
-            start: range.start,
-            end: range.end,
-        }
+        Self::new(range.start, range.end)
     }
 }
 
@@ -71,16 +94,11 @@ impl chumsky::span::Span for Span {
 
     type Offset = u32;
 
-    fn new(context: Self::Context, range: Range<Self::Offset>) -> Self {
-        Self {
-            start: range.start,
-            end: range.end,
-        }
+    fn new(_context: Self::Context, range: Range<Self::Offset>) -> Self {
+        Span::new(range.start, range.end)
     }
 
-    fn context(&self) -> Self::Context {
-        ()
-    }
+    fn context(&self) -> Self::Context {}
 
     fn start(&self) -> Self::Offset {
         self.start
@@ -106,3 +124,192 @@ impl Index<Span> for String {
         &self[Range::from(index)]
     }
 }
+
+// Line and column lookup for spans into one source. The line starts are
+// found once, so each lookup is a binary search. Lines and columns count
+// from 1, and columns count chars rather than bytes.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SourceMap {
+    src: String,
+    // the offset of the first byte of each line
+    line_starts: Vec<u32>,
+}
+
+impl SourceMap {
+    pub fn new(src: &str) -> Self {
+        Self {
+            src: src.to_string(),
+            line_starts: std::iter::once(0)
+                .chain(src.match_indices('\n').map(|(i, _)| i as u32 + 1))
+                .collect(),
+        }
+    }
+
+    pub fn src(&self) -> &str {
+        &self.src
+    }
+
+    pub fn line_count(&self) -> usize {
+        self.line_starts.len()
+    }
+
+    // the line and column the span starts at
+    pub fn line_col(&self, span: Span) -> (usize, usize) {
+        let offset = span.start.min(self.src.len() as u32);
+        let line = self.line_of(offset);
+        let start = self.line_starts[line - 1] as usize;
+        (line, self.src[start..offset as usize].chars().count() + 1)
+    }
+
+    // the text of a line without its line ending
+    pub fn line_text(&self, line: usize) -> Option<&str> {
+        let start = *self.line_starts.get(line.checked_sub(1)?)? as usize;
+        let end = self
+            .line_starts
+            .get(line)
+            .map_or(self.src.len(), |&end| end as usize);
+        let text = &self.src[start..end];
+        let text = text.strip_suffix('\n').unwrap_or(text);
+        Some(text.strip_suffix('\r').unwrap_or(text))
+    }
+
+    // The lines the span covers with the span underlined, like
+    //
+    //     2 | (f (g x)
+    //       |    ^^^^^
+    //
+    // An empty span gets a single ^ where it is.
+    pub fn snippet(&self, span: Span) -> String {
+        let len = self.src.len() as u32;
+        let first = self.line_of(span.start.min(len));
+        // a span that ends with a newline doesn't reach the next line
+        let last = self.line_of(span.end.saturating_sub(1).max(span.start).min(len));
+        let width = last.to_string().len();
+        let mut out = String::new();
+        for line in first..=last {
+            let text = self.line_text(line).unwrap_or("");
+            let start = self.line_starts[line - 1];
+            let col = |offset: u32| {
+                let offset = (offset.max(start) - start) as usize;
+                text[..offset.min(text.len())].chars().count()
+            };
+            let (from, to) = (col(span.start), col(span.end));
+            out.push_str(&format!("{:>width$} | {}\n", line, text));
+            out.push_str(&format!(
+                "{:width$} | {}{}\n",
+                "",
+                " ".repeat(from),
+                "^".repeat((to - from).max(1))
+            ));
+        }
+        out
+    }
+
+    fn line_of(&self, offset: u32) -> usize {
+        self.line_starts.partition_point(|&start| start <= offset)
+    }
+}
+
+// Owns the text of every source that's been read, so a diagnostic can find
+// the name and lines of the file its span is in.
+#[derive(Debug, Clone, Default)]
+pub struct SourceCache {
+    files: Vec<(String, SourceMap)>,
+}
+
+impl SourceCache {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    // The name is only for showing, like a path or <stdin>. Spans read from
+    // `src` should be put in the returned file.
+    pub fn add(&mut self, name: &str, src: &str) -> FileId {
+        self.files.push((name.to_string(), SourceMap::new(src)));
+        FileId(self.files.len() as u32)
+    }
+
+    pub fn name(&self, file: FileId) -> Option<&str> {
+        self.get(file).map(|(name, _)| name.as_str())
+    }
+
+    pub fn map(&self, file: FileId) -> Option<&SourceMap> {
+        self.get(file).map(|(_, map)| map)
+    }
+
+    pub fn src(&self, file: FileId) -> Option<&str> {
+        self.map(file).map(SourceMap::src)
+    }
+
+    // name:line:col of where the span starts
+    pub fn location(&self, span: Span) -> Option<String> {
+        let (name, map) = self.get(span.file)?;
+        let (line, col) = map.line_col(span);
+        Some(format!("{}:{}:{}", name, line, col))
+    }
+
+    // ids start at 1, the default file isn't in any cache
+    fn get(&self, file: FileId) -> Option<&(String, SourceMap)> {
+        self.files.get((file.0 as usize).checked_sub(1)?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{FileId, SourceCache, SourceMap, Span};
+
+    #[test]
+    fn line_col() {
+        let map = SourceMap::new("(f\r\n  (g \"λ\" x))\n");
+        assert_eq!(map.line_count(), 3);
+        assert_eq!(map.line_col(Span::new(0, 1)), (1, 1));
+        assert_eq!(map.line_col(Span::new(6, 14)), (2, 3));
+        // λ is two bytes but one column
+        assert_eq!(map.line_col(Span::new(14, 15)), (2, 10));
+        assert_eq!(map.line_col(Span::new(18, 18)), (3, 1));
+        assert_eq!(map.line_text(1), Some("(f"));
+        assert_eq!(map.line_text(2), Some("  (g \"λ\" x))"));
+        assert_eq!(map.line_text(3), Some(""));
+        assert_eq!(map.line_text(0), None);
+        assert_eq!(map.line_text(4), None);
+    }
+
+    #[test]
+    fn snippet() {
+        let map = SourceMap::new("(def x 1)\n(f (g x)\n   y)\n");
+        assert_eq!(
+            map.snippet(Span::new(13, 18)),
+            "2 | (f (g x)\n  |    ^^^^^\n"
+        );
+        assert_eq!(map.snippet(Span::new(10, 16)), "2 | (f (g x)\n  | ^^^^^^\n");
+        assert_eq!(
+            map.snippet(Span::new(10, 24)),
+            "2 | (f (g x)\n  | ^^^^^^^^\n3 |    y)\n  | ^^^^^\n"
+        );
+        assert_eq!(
+            map.snippet(Span::new(8, 8)),
+            "1 | (def x 1)\n  |         ^\n"
+        );
+    }
+
+    #[test]
+    fn source_cache() {
+        let mut cache = SourceCache::new();
+        let main = cache.add("main.lust", "(import util)\n(f x)\n");
+        let util = cache.add("util.lust", "(def (f x) x)\n");
+        assert_ne!(main, util);
+        let span = Span::new(15, 16);
+        assert_eq!(cache.location(span.in_file(main)).unwrap(), "main.lust:2:2");
+        assert_eq!(
+            cache.location(Span::new(5, 6).in_file(util)).unwrap(),
+            "util.lust:1:6"
+        );
+        assert_eq!(cache.location(span), None);
+        assert_eq!(cache.name(util), Some("util.lust"));
+        assert_eq!(cache.src(util), Some("(def (f x) x)\n"));
+        assert_eq!(cache.src(FileId::default()), None);
+        // a span is still the same offsets in another file
+        assert_ne!(span.in_file(main), span);
+        assert_eq!(span.in_file(main).shift(1).file(), main);
+    }
+}
@@ -0,0 +1,364 @@
+/*
+ * A persistent vector, implemented as a 32-way trie of leaves with the
+ * last leaf kept outside the trie as a tail. Indexing and updates touch
+ * O(log32 n) nodes and an update copies only the path to the changed
+ * element. Pushing and popping mostly just copy the tail.
+ *
+ * Concatenation pushes the elements of one vector onto the other, so it
+ * costs O(m log n) rather than the O(log n) an RRB tree would give.
+ */
+use std::{fmt::Debug, rc::Rc};
+
+const BITS: u32 = 5;
+const WIDTH: usize = 1 << BITS;
+const MASK: usize = WIDTH - 1;
+
+pub struct Vector<T> {
+    root: Rc<Node<T>>,
+    tail: Rc<Vec<T>>,
+    // the shift of the root's level, which is BITS when its children are
+    // leaves
+    shift: u32,
+    len: usize,
+}
+
+#[derive(Debug, Clone)]
+enum Node<T> {
+    Branch(Vec<Rc<Node<T>>>),
+    Leaf(Vec<T>),
+}
+
+impl<T> Node<T> {
+    fn children(&self) -> &[Rc<Node<T>>] {
+        match self {
+            Node::Branch(children) => children,
+            Node::Leaf(_) => unreachable!("leaves only sit at the bottom level"),
+        }
+    }
+
+    fn elements(&self) -> &[T] {
+        match self {
+            Node::Leaf(elements) => elements,
+            Node::Branch(_) => unreachable!("branches never sit at the bottom level"),
+        }
+    }
+}
+
+impl<T> Vector<T> {
+    pub fn new() -> Self {
+        Self {
+            root: Rc::new(Node::Branch(vec![])),
+            tail: Rc::new(vec![]),
+            shift: BITS,
+            len: 0,
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    pub fn get(&self, index: usize) -> Option<&T> {
+        if index >= self.len {
+            return None;
+        }
+        Some(&self.chunk(index)[index & MASK])
+    }
+
+    pub fn first(&self) -> Option<&T> {
+        self.get(0)
+    }
+
+    pub fn last(&self) -> Option<&T> {
+        self.len.checked_sub(1).and_then(|i| self.get(i))
+    }
+
+    pub fn iter(&self) -> Iter<'_, T> {
+        Iter {
+            vector: self,
+            index: 0,
+            chunk: [].iter(),
+        }
+    }
+
+    // the index of the first element in the tail
+    fn tail_offset(&self) -> usize {
+        if self.len < WIDTH {
+            0
+        } else {
+            ((self.len - 1) >> BITS) << BITS
+        }
+    }
+
+    // the leaf, or the tail, holding `index`
+    fn chunk(&self, index: usize) -> &[T] {
+        if index >= self.tail_offset() {
+            return &self.tail;
+        }
+        let mut node = &*self.root;
+        let mut level = self.shift;
+        while level > 0 {
+            node = &node.children()[(index >> level) & MASK];
+            level -= BITS;
+        }
+        node.elements()
+    }
+}
+
+impl<T: Clone> Vector<T> {
+    // Returns a new vector with `value` at `index`, leaving this one as
+    // is. Panics if `index` is out of bounds.
+    pub fn set(&self, index: usize, value: T) -> Self {
+        assert!(index < self.len, "index {} out of bounds", index);
+        if index >= self.tail_offset() {
+            let mut tail = (*self.tail).clone();
+            tail[index & MASK] = value;
+            return Self {
+                root: self.root.clone(),
+                tail: Rc::new(tail),
+                shift: self.shift,
+                len: self.len,
+            };
+        }
+        Self {
+            root: Rc::new(set(&self.root, self.shift, index, value)),
+            tail: self.tail.clone(),
+            shift: self.shift,
+            len: self.len,
+        }
+    }
+
+    // Returns a new vector with `value` on the end, leaving this one as is.
+    pub fn push(&self, value: T) -> Self {
+        if self.len - self.tail_offset() < WIDTH {
+            let mut tail = (*self.tail).clone();
+            tail.push(value);
+            return Self {
+                root: self.root.clone(),
+                tail: Rc::new(tail),
+                shift: self.shift,
+                len: self.len + 1,
+            };
+        }
+        // the tail is full, so it goes into the trie as a leaf
+        let leaf = Rc::new(Node::Leaf((*self.tail).clone()));
+        let (root, shift) = if (self.len >> BITS) > (1 << self.shift) {
+            // no room under the root, so the trie grows a level
+            let root = Node::Branch(vec![self.root.clone(), new_path(self.shift, leaf)]);
+            (root, self.shift + BITS)
+        } else {
+            (
+                push_tail(&self.root, self.shift, self.len, leaf),
+                self.shift,
+            )
+        };
+        Self {
+            root: Rc::new(root),
+            tail: Rc::new(vec![value]),
+            shift,
+            len: self.len + 1,
+        }
+    }
+
+    // Returns a new vector without its last element, leaving this one as
+    // is. Popping an empty vector gives an empty vector.
+    pub fn pop(&self) -> Self {
+        match self.len {
+            0 | 1 => return Self::new(),
+            _ => (),
+        }
+        if self.len - self.tail_offset() > 1 {
+            let mut tail = (*self.tail).clone();
+            tail.pop();
+            return Self {
+                root: self.root.clone(),
+                tail: Rc::new(tail),
+                shift: self.shift,
+                len: self.len - 1,
+            };
+        }
+        // the tail is emptied, so the last leaf comes out of the trie to
+        // replace it
+        let tail = self.chunk(self.len - 2).to_vec();
+        let mut root = pop_tail(&self.root, self.shift, self.len)
+            .map(Rc::new)
+            .unwrap_or_else(|| Rc::new(Node::Branch(vec![])));
+        let mut shift = self.shift;
+        if shift > BITS && root.children().len() == 1 {
+            root = root.children()[0].clone();
+            shift -= BITS;
+        }
+        Self {
+            root,
+            tail: Rc::new(tail),
+            shift,
+            len: self.len - 1,
+        }
+    }
+
+    // Returns a new vector with the elements of `other` after the elements
+    // of this one.
+    pub fn concat(&self, other: &Self) -> Self {
+        other
+            .iter()
+            .fold(self.clone(), |vector, value| vector.push(value.clone()))
+    }
+}
+
+fn set<T: Clone>(node: &Node<T>, level: u32, index: usize, value: T) -> Node<T> {
+    match node {
+        Node::Leaf(elements) => {
+            let mut elements = elements.clone();
+            elements[index & MASK] = value;
+            Node::Leaf(elements)
+        }
+        Node::Branch(children) => {
+            let i = (index >> level) & MASK;
+            let mut children = children.clone();
+            children[i] = Rc::new(set(&children[i], level - BITS, index, value));
+            Node::Branch(children)
+        }
+    }
+}
+
+// a chain of branches down from `level` ending in `leaf`
+fn new_path<T>(level: u32, leaf: Rc<Node<T>>) -> Rc<Node<T>> {
+    if level == 0 {
+        leaf
+    } else {
+        Rc::new(Node::Branch(vec![new_path(level - BITS, leaf)]))
+    }
+}
+
+// `len` is the length of the vector whose full tail is being pushed
+fn push_tail<T: Clone>(node: &Node<T>, level: u32, len: usize, leaf: Rc<Node<T>>) -> Node<T> {
+    let i = ((len - 1) >> level) & MASK;
+    let mut children = node.children().to_vec();
+    if level == BITS {
+        children.push(leaf);
+    } else if i < children.len() {
+        children[i] = Rc::new(push_tail(&children[i], level - BITS, len, leaf));
+    } else {
+        children.push(new_path(level - BITS, leaf));
+    }
+    Node::Branch(children)
+}
+
+// `len` is the length of the vector whose last leaf is being removed.
+// Returns None if the node is left empty.
+fn pop_tail<T: Clone>(node: &Node<T>, level: u32, len: usize) -> Option<Node<T>> {
+    let i = ((len - 2) >> level) & MASK;
+    let mut children = node.children().to_vec();
+    if level > BITS {
+        let child = pop_tail(&children[i], level - BITS, len);
+        children.truncate(i);
+        children.extend(child.map(Rc::new));
+    } else {
+        children.truncate(i);
+    }
+    (!children.is_empty()).then_some(Node::Branch(children))
+}
+
+impl<T> Clone for Vector<T> {
+    fn clone(&self) -> Self {
+        Self {
+            root: self.root.clone(),
+            tail: self.tail.clone(),
+            shift: self.shift,
+            len: self.len,
+        }
+    }
+}
+
+impl<T> Default for Vector<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T: Debug> Debug for Vector<T> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_list().entries(self.iter()).finish()
+    }
+}
+
+impl<T: PartialEq> PartialEq for Vector<T> {
+    fn eq(&self, other: &Self) -> bool {
+        self.len == other.len && self.iter().eq(other.iter())
+    }
+}
+
+impl<T: Clone> FromIterator<T> for Vector<T> {
+    fn from_iter<I: IntoIterator<Item = T>>(iter: I) -> Self {
+        iter.into_iter()
+            .fold(Self::new(), |vector, value| vector.push(value))
+    }
+}
+
+pub struct Iter<'a, T> {
+    vector: &'a Vector<T>,
+    index: usize,
+    // what's left of the current leaf
+    chunk: std::slice::Iter<'a, T>,
+}
+
+impl<'a, T> Iterator for Iter<'a, T> {
+    type Item = &'a T;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.index >= self.vector.len {
+            return None;
+        }
+        if self.index & MASK == 0 {
+            self.chunk = self.vector.chunk(self.index).iter();
+        }
+        self.index += 1;
+        self.chunk.next()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Vector;
+
+    #[test]
+    fn persistent() {
+        let a = Vector::new().push(1).push(2);
+        let b = a.set(0, 3).push(4);
+        let c = b.pop().pop();
+        assert_eq!(a.iter().copied().collect::<Vec<_>>(), [1, 2]);
+        assert_eq!(b.iter().copied().collect::<Vec<_>>(), [3, 2, 4]);
+        assert_eq!(c.iter().copied().collect::<Vec<_>>(), [3]);
+        assert_eq!((c.first(), c.last()), (Some(&3), Some(&3)));
+        assert_eq!(a.get(2), None);
+    }
+
+    #[test]
+    fn many() {
+        let vector = (0..10_000).collect::<Vector<_>>();
+        assert_eq!(vector.len(), 10_000);
+        assert!((0..10_000).all(|i| vector.get(i) == Some(&i)));
+        assert!(vector.iter().copied().eq(0..10_000));
+        let doubled = (0..10_000).fold(vector.clone(), |v, i| v.set(i, i * 2));
+        assert!((0..10_000).all(|i| doubled.get(i) == Some(&(i * 2))));
+        assert_eq!(vector.get(9_999), Some(&9_999));
+        let half = (0..5_000).fold(vector.clone(), |v, _| v.pop());
+        assert!(half.iter().copied().eq(0..5_000));
+        assert_eq!(half.push(5_000).get(5_000), Some(&5_000));
+        let empty = (0..10_000).fold(vector, |v, _| v.pop());
+        assert!(empty.is_empty());
+        assert_eq!(empty, Vector::new());
+    }
+
+    #[test]
+    fn concat() {
+        let a = (0..100).collect::<Vector<_>>();
+        let b = (100..250).collect::<Vector<_>>();
+        assert!(a.concat(&b).iter().copied().eq(0..250));
+        assert_eq!(a.len(), 100);
+    }
+}
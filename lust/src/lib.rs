@@ -0,0 +1,25 @@
+/*
+ * The public API: reading, expanding and checking source. Parser and lexer
+ * library types stay behind these, so changing either doesn't change
+ * anything here. There's no evaluator yet, so nothing runs code.
+ */
+pub use lust_rename::check::{
+    check, check_with, CheckOptions, Diagnostic, DiagnosticKind, Fix, Severity,
+};
+pub use lust_syntax::{
+    expand::{
+        error::{ExpandError, ExpandErrorKind},
+        Expander,
+    },
+    read::{
+        error::SyntaxError,
+        lex, parse_datum, read, read_one,
+        sexpr::{Atom, AtomKind, Lit, Root, Sexpr, SexprKind},
+        token::Token,
+    },
+};
+pub use lust_utils::{
+    intern::InternedString,
+    list::List,
+    span::{FileId, SourceCache, SourceMap, Span},
+};
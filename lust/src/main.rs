@@ -1,6 +1,171 @@
-use lust_repl::repl;
+use clap::{Parser, Subcommand};
+use lust_rename::{
+    check::{check_with, CheckOptions, Severity},
+    tail::tail_calls,
+};
+use lust_repl::{default_rc, repl_with, ReplOptions};
+use lust_syntax::{
+    expand::Expander,
+    read::{
+        corpus::{generate, CorpusOptions},
+        decode,
+        options::ReaderOptions,
+        read_bytes_with,
+    },
+};
+use std::{path::PathBuf, process::exit};
+
+mod tokens;
+
+#[derive(Parser)]
+#[command(version, about)]
+struct Cli {
+    #[command(subcommand)]
+    command: Option<Command>,
+    /// file the REPL loads on startup instead of ~/.lustrc
+    #[arg(long)]
+    rc: Option<PathBuf>,
+    /// start the REPL without loading an rc file
+    #[arg(long, conflicts_with = "rc")]
+    no_rc: bool,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Report unbound names and arity mismatches without running anything
+    Check {
+        /// files to check
+        paths: Vec<PathBuf>,
+        /// don't warn about definitions that are never used
+        #[arg(long)]
+        allow_unused: bool,
+        /// list recursive calls and whether each one is in tail position
+        #[arg(long)]
+        annotate_tail_calls: bool,
+        /// read symbols case-insensitively, as if every file began with
+        /// #!fold-case
+        #[arg(long)]
+        fold_case: bool,
+        /// print each file's tokens first, as a table or as json
+        #[arg(
+            long,
+            value_enum,
+            num_args = 0..=1,
+            require_equals = true,
+            default_missing_value = "table"
+        )]
+        dump_tokens: Option<tokens::Format>,
+    },
+    /// Print a pseudo-random source file for benchmarking and fuzzing the
+    /// reader, the same seed always gives the same file
+    GenCorpus {
+        /// seed for the generator
+        #[arg(long, default_value_t = 0)]
+        seed: u64,
+        /// rough size in bytes
+        #[arg(long, default_value_t = 64 * 1024)]
+        size: usize,
+        /// lists deeper than this only hold atoms
+        #[arg(long, default_value_t = 8)]
+        max_depth: usize,
+        /// the most items in one list
+        #[arg(long, default_value_t = 8)]
+        max_width: usize,
+    },
+}
 
 fn main() {
     env_logger::init();
-    repl()
+    let cli = Cli::parse();
+    match cli.command {
+        Some(Command::Check {
+            paths,
+            allow_unused,
+            annotate_tail_calls,
+            fold_case,
+            dump_tokens,
+        }) => exit(check_files(
+            &paths,
+            &ReaderOptions {
+                case_insensitive: fold_case,
+                ..Default::default()
+            },
+            &CheckOptions {
+                warn_unused: !allow_unused,
+            },
+            annotate_tail_calls,
+            dump_tokens,
+        )),
+        Some(Command::GenCorpus {
+            seed,
+            size,
+            max_depth,
+            max_width,
+        }) => print!(
+            "{}",
+            generate(&CorpusOptions {
+                seed,
+                size,
+                max_depth,
+                max_width,
+                ..Default::default()
+            })
+        ),
+        None => repl_with(&ReplOptions {
+            rc: if cli.no_rc {
+                None
+            } else {
+                cli.rc.or_else(default_rc)
+            },
+        }),
+    }
+}
+
+fn check_files(
+    paths: &[PathBuf],
+    reader_opts: &ReaderOptions,
+    opts: &CheckOptions,
+    annotate_tail_calls: bool,
+    dump_tokens: Option<tokens::Format>,
+) -> i32 {
+    let mut failed = false;
+    for path in paths {
+        let src = match std::fs::read(path) {
+            Ok(src) => src,
+            Err(err) => {
+                eprintln!("{}: {}", path.display(), err);
+                failed = true;
+                continue;
+            }
+        };
+        if let Some(format) = dump_tokens {
+            // the same text the reader sees, so the spans are the same
+            print!("{}", tokens::dump(&decode(&src).0, format));
+        }
+        let (root, errors) = read_bytes_with(&src, reader_opts);
+        for err in &errors {
+            eprintln!("{}:{}", path.display(), err);
+        }
+        failed |= !errors.is_empty();
+        let Some(root) = root else { continue };
+        let (root, errors) = Expander::new().expand(root, Some(path));
+        for err in &errors {
+            eprintln!("{}:{}", path.display(), err);
+        }
+        failed |= !errors.is_empty();
+        for diagnostic in check_with(&root, opts) {
+            eprintln!("{}:{}", path.display(), diagnostic);
+            failed |= diagnostic.severity() == Severity::Error;
+        }
+        if annotate_tail_calls {
+            for call in tail_calls(&root) {
+                println!("{}:{}", path.display(), call);
+            }
+        }
+    }
+    if failed {
+        1
+    } else {
+        0
+    }
 }
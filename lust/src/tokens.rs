@@ -0,0 +1,105 @@
+/*
+ * `lust check --dump-tokens` prints the raw token stream of each file,
+ * comments included, for debugging the lexer. The table is for reading,
+ * `--dump-tokens=json` gives one object per token for tooling.
+ */
+use clap::ValueEnum;
+use lust_syntax::lex;
+use lust_utils::span::SourceMap;
+use std::fmt::Write;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum Format {
+    Table,
+    Json,
+}
+
+pub fn dump(src: &str, format: Format) -> String {
+    let map = SourceMap::new(src);
+    let (tokens, _) = lex(src);
+    let mut out = String::new();
+    if format == Format::Json {
+        out.push('[');
+    }
+    let mut first = true;
+    for (tok, span) in tokens {
+        let (start, end) = (span.start() as usize, span.end() as usize);
+        let (line, col) = map.line_col(span);
+        let text = &src[start..end];
+        match format {
+            Format::Table => writeln!(
+                out,
+                "{:>4}:{:<4} {:>10}  {:<24} {:?}",
+                line,
+                col,
+                span.to_string(),
+                tok.to_string(),
+                text
+            ),
+            Format::Json => write!(
+                out,
+                "{}\n  {{\"token\": {}, \"text\": {}, \"start\": {}, \"end\": {}, \"line\": {}, \"col\": {}}}",
+                if first { "" } else { "," },
+                json_string(&tok.to_string()),
+                json_string(text),
+                start,
+                end,
+                line,
+                col
+            ),
+        }
+        .unwrap();
+        first = false;
+    }
+    if format == Format::Json {
+        out.push_str("\n]\n");
+    }
+    out
+}
+
+fn json_string(s: &str) -> String {
+    let mut out = String::from("\"");
+    for c in s.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\t' => out.push_str("\\t"),
+            c if c.is_control() => write!(out, "\\u{:04x}", c as u32).unwrap(),
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{dump, Format};
+
+    #[test]
+    fn table() {
+        assert_eq!(
+            dump("(f\n  \"x\")", Format::Table),
+            concat!(
+                "   1:1          0..1  (                        \"(\"\n",
+                "   1:2          1..2  Ident(f)                 \"f\"\n",
+                "   2:3          5..8  String(\"x\")              \"\\\"x\\\"\"\n",
+                "   2:6          8..9  )                        \")\"\n",
+            )
+        );
+    }
+
+    #[test]
+    fn json() {
+        assert_eq!(
+            dump("a ; b", Format::Json),
+            concat!(
+                "[\n",
+                "  {\"token\": \"Ident(a)\", \"text\": \"a\", \"start\": 0, \"end\": 1, \"line\": 1, \"col\": 1},\n",
+                "  {\"token\": \"Comment\", \"text\": \"; b\", \"start\": 2, \"end\": 5, \"line\": 1, \"col\": 3}\n",
+                "]\n",
+            )
+        );
+    }
+}